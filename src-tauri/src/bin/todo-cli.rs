@@ -0,0 +1,789 @@
+use std::path::PathBuf;
+
+use chrono::{Duration, Local, NaiveDate};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::Serialize;
+use todo_list::{
+    app_data_dir,
+    blob_store::BlobStore,
+    config::AppConfig,
+    database::{init_sqlite_with, DATABASE_URL},
+    service::{
+        Activity, Attachment, EditMode, Estimate, PriorityLevel, SavedSearch, SearchFilter, Stats,
+        TagMeta, TagStats, TodoItem, TodoService,
+    },
+    util::ErrMapString,
+};
+
+/// manage the todo list without the gui
+#[derive(Debug, Parser)]
+#[clap(name = "todo-cli")]
+struct Cli {
+    /// the database shared with the gui
+    #[clap(long, default_value = DATABASE_URL)]
+    database: String,
+    /// the attachment store shared with the gui, the one in the config or under the app
+    /// data directory by default
+    #[clap(long)]
+    attachments: Option<PathBuf>,
+    /// print json instead of a table
+    #[clap(long, global = true)]
+    json: bool,
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// add a new todo item
+    Add {
+        message: String,
+        #[clap(short, long, value_enum, default_value = "medium")]
+        priority: Priority,
+        #[clap(short, long = "tag")]
+        tags: Vec<String>,
+    },
+    /// add a todo item typed in one line, like `Fix login bug !high #backend tomorrow`
+    Quick { line: String },
+    /// list todo items
+    List {
+        /// only the items bind with this tag
+        #[clap(short, long)]
+        tag: Option<String>,
+        /// with `--tag`, include the items bind with any child tag
+        #[clap(short, long)]
+        descendants: bool,
+        /// hide the done items
+        #[clap(long)]
+        open: bool,
+    },
+    /// mark a todo item as done
+    Done { item_id: i32 },
+    /// edit the message or priority of a todo item
+    Edit {
+        item_id: i32,
+        #[clap(short, long)]
+        message: Option<String>,
+        #[clap(short, long, value_enum)]
+        priority: Option<Priority>,
+    },
+    /// show the markdown notes of a todo item
+    Notes {
+        item_id: i32,
+        /// replace the notes instead
+        #[clap(long)]
+        set: Option<String>,
+    },
+    /// list the todo items whose message or notes contain the keyword
+    Search { keyword: String },
+    /// attach a file to a todo item
+    Attach { item_id: i32, path: PathBuf },
+    /// list the files attached to a todo item
+    Attachments { item_id: i32 },
+    /// open an attachment with the default app
+    Open { attachment_id: i32 },
+    /// remove an attachment
+    Detach { attachment_id: i32 },
+    /// comment on a todo item
+    Comment {
+        item_id: i32,
+        body: String,
+        #[clap(short, long, default_value = "cli")]
+        author: String,
+    },
+    /// list the comments and the changes of a todo item
+    Activity { item_id: i32 },
+    /// the todo item can not be done before the blocker is done
+    Block { item_id: i32, blocker_id: i32 },
+    /// remove a dependency added by `block`
+    Unblock { item_id: i32, blocker_id: i32 },
+    /// set the estimate of a todo item, without any option clear it
+    Estimate {
+        item_id: i32,
+        #[clap(long, conflicts_with = "points")]
+        minutes: Option<u32>,
+        #[clap(long)]
+        points: Option<u32>,
+    },
+    /// start the timer on a todo item, the running timer is stopped first
+    Start { item_id: i32 },
+    /// stop the running timer
+    Stop,
+    /// the items created and completed per day, the completion rates and the streaks
+    Stats {
+        /// the first day, 6 days before `--to` by default
+        #[clap(long)]
+        from: Option<NaiveDate>,
+        /// the last day, today by default
+        #[clap(long)]
+        to: Option<NaiveDate>,
+        /// count per week instead of per day
+        #[clap(long)]
+        weekly: bool,
+    },
+    /// list the saved searches, or the todo items match the saved search with the name
+    Saved {
+        name: Option<String>,
+        /// save the filter in json with the name instead, like
+        /// `{"kind":"Done","done":false}`. the saved search with the name is replaced
+        #[clap(long, requires = "name", conflicts_with = "delete")]
+        filter: Option<String>,
+        /// delete the saved search with the name instead
+        #[clap(long, requires = "name")]
+        delete: bool,
+    },
+    /// bind tags with a todo item
+    Tag { item_id: i32, tags: Vec<String> },
+    /// remove tags from a todo item
+    Untag { item_id: i32, tags: Vec<String> },
+    /// remove a todo item
+    Rm { item_id: i32 },
+    /// list all tags
+    Tags {
+        /// show how many open and done items use the tag
+        #[clap(long)]
+        stats: bool,
+    },
+    /// the tags match the input, the often and recently used first
+    Suggest {
+        prefix: String,
+        #[clap(short, long, default_value = "10")]
+        limit: u64,
+    },
+    /// remove the tags no item use
+    Prune {
+        /// only list the tags would be removed
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// merge the source tags into the target tag, by the ids listed in `tags`
+    Merge {
+        target_id: i32,
+        #[clap(required = true)]
+        source_ids: Vec<i32>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Priority {
+    VeryHigh,
+    High,
+    Medium,
+    Low,
+    VeryLow,
+}
+
+impl From<Priority> for PriorityLevel {
+    fn from(val: Priority) -> Self {
+        match val {
+            Priority::VeryHigh => PriorityLevel::VeryHigh,
+            Priority::High => PriorityLevel::High,
+            Priority::Medium => PriorityLevel::Medium,
+            Priority::Low => PriorityLevel::Low,
+            Priority::VeryLow => PriorityLevel::VeryLow,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ItemRow {
+    id: i32,
+    #[serde(flatten)]
+    item: TodoItem,
+}
+
+#[derive(Debug, Serialize)]
+struct TagRow {
+    id: i32,
+    value: String,
+    #[serde(flatten)]
+    meta: TagMeta,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let config = AppConfig::load().unwrap_or_else(|err| {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    });
+    let attachment_dir = cli
+        .attachments
+        .clone()
+        .unwrap_or_else(|| config.attachment.dir_or(app_data_dir()));
+    let service = TodoService::new(init_sqlite_with(&cli.database).await)
+        .with_tag_policy(config.tag)
+        .with_limits(config.limits)
+        .with_blob_store(BlobStore::new(attachment_dir));
+
+    // the database may be created by an older version or under another tag policy
+    match service.normalize_tags().await {
+        Ok(collisions) => {
+            for collision in collisions {
+                eprintln!(
+                    "warning: tags {:?} are the same tag `{}`, merge them to clean up",
+                    collision.tags, collision.key
+                );
+            }
+        }
+        Err(err) => eprintln!("warning: normalize tags failure: {err}"),
+    }
+
+    if let Err(err) = run(&service, cli.command, cli.json).await {
+        eprintln!("error: {err}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(service: &TodoService, command: Command, json: bool) -> Result<(), String> {
+    match command {
+        Command::Add {
+            message,
+            priority,
+            tags,
+        } => {
+            let id = service
+                .save_todo_item(TodoItem {
+                    message,
+                    priority: priority.into(),
+                    done: false,
+                    blocked: false,
+                    estimate: None,
+                    due: None,
+                    tags,
+                })
+                .await
+                .err_to_str()?;
+            print_id(id, json);
+        }
+        Command::Quick { line } => {
+            let today = Local::now().date().naive_local();
+            let (id, _) = service.quick_add(&line, today).await.err_to_str()?;
+            print_id(id, json);
+        }
+        Command::List {
+            tag,
+            descendants,
+            open,
+        } => {
+            let items = match tag {
+                Some(tag) => {
+                    let tag_id = service.get_tag_id(tag).await.err_to_str()?;
+                    service
+                        .fetch_all_tag_todo_item(tag_id, descendants)
+                        .await
+                        .err_to_str()?
+                }
+                None => service.fetch_all_todo_item().await.err_to_str()?,
+            };
+            let rows = items
+                .into_iter()
+                .filter(|(_, item)| !open || !item.done)
+                .map(|(id, item)| ItemRow { id, item })
+                .collect::<Vec<_>>();
+            print_items(&rows, json);
+        }
+        Command::Done { item_id } => {
+            let state = service.set_done(item_id, true).await.err_to_str()?;
+            if state.rows_affected == 0 {
+                return Err(format!("todo item {item_id} not found"));
+            }
+            print_id(item_id, json);
+        }
+        Command::Edit {
+            item_id,
+            message,
+            priority,
+        } => {
+            if let Some(message) = message {
+                service.edit_message(item_id, message).await.err_to_str()?;
+            }
+            if let Some(priority) = priority {
+                service
+                    .edit_priority(item_id, priority.into())
+                    .await
+                    .err_to_str()?;
+            }
+            print_id(item_id, json);
+        }
+        Command::Notes {
+            item_id,
+            set: Some(notes),
+        } => {
+            service.edit_notes(item_id, notes).await.err_to_str()?;
+            print_id(item_id, json);
+        }
+        Command::Notes { item_id, set: None } => {
+            let notes = service.fetch_notes(item_id).await.err_to_str()?;
+            if json {
+                println!("{}", serde_json::json!({ "id": item_id, "notes": notes }));
+            } else {
+                println!("{notes}");
+            }
+        }
+        Command::Search { keyword } => {
+            let rows = service
+                .search_todo_item(keyword)
+                .await
+                .err_to_str()?
+                .into_iter()
+                .map(|(id, item)| ItemRow { id, item })
+                .collect::<Vec<_>>();
+            print_items(&rows, json);
+        }
+        Command::Attach { item_id, path } => {
+            let attachment = service.add_attachment(item_id, path).await.err_to_str()?;
+            print_id(attachment.id, json);
+        }
+        Command::Attachments { item_id } => {
+            let rows = service.fetch_attachments(item_id).await.err_to_str()?;
+            print_attachments(&rows, json);
+        }
+        Command::Open { attachment_id } => {
+            let path = service
+                .export_attachment(attachment_id)
+                .await
+                .err_to_str()?;
+            open::that(path).err_to_str()?;
+        }
+        Command::Detach { attachment_id } => {
+            service
+                .remove_attachment(attachment_id)
+                .await
+                .err_to_str()?;
+            print_id(attachment_id, json);
+        }
+        Command::Comment {
+            item_id,
+            body,
+            author,
+        } => {
+            let comment = service
+                .add_comment(item_id, author, body)
+                .await
+                .err_to_str()?;
+            print_id(comment.id, json);
+        }
+        Command::Activity { item_id } => {
+            let rows = service.fetch_activity(item_id).await.err_to_str()?;
+            print_activity(&rows, json);
+        }
+        Command::Block {
+            item_id,
+            blocker_id,
+        } => {
+            service
+                .add_dependency(item_id, blocker_id)
+                .await
+                .err_to_str()?;
+            print_id(item_id, json);
+        }
+        Command::Unblock {
+            item_id,
+            blocker_id,
+        } => {
+            service
+                .remove_dependency(item_id, blocker_id)
+                .await
+                .err_to_str()?;
+            print_id(item_id, json);
+        }
+        Command::Estimate {
+            item_id,
+            minutes,
+            points,
+        } => {
+            let estimate = minutes
+                .map(Estimate::Minutes)
+                .or_else(|| points.map(Estimate::Points));
+            service.set_estimate(item_id, estimate).await.err_to_str()?;
+            print_id(item_id, json);
+        }
+        Command::Start { item_id } => {
+            let entry = service.start_timer(item_id).await.err_to_str()?;
+            print_id(entry.id, json);
+        }
+        Command::Stop => match service.stop_timer().await.err_to_str()? {
+            Some(entry) if json => println!(
+                "{}",
+                serde_json::to_string_pretty(&entry).expect("Unreachable")
+            ),
+            Some(entry) => println!("{}: {}s", entry.item_id, entry.seconds),
+            None => return Err("no timer is running".into()),
+        },
+        Command::Stats { from, to, weekly } => {
+            let now = Local::now();
+            let to = to.unwrap_or_else(|| now.date().naive_local());
+            let from = from.unwrap_or(to - Duration::days(6));
+            let utc_offset = now.offset().local_minus_utc() / 60;
+            let stats = service
+                .fetch_stats(from, to, utc_offset)
+                .await
+                .err_to_str()?;
+            print_stats(&stats, weekly, json);
+        }
+        Command::Saved {
+            name,
+            filter,
+            delete,
+        } => {
+            let searches = service.fetch_saved_searches().await.err_to_str()?;
+            let name = match name {
+                Some(name) => name,
+                None => {
+                    print_saved_searches(&searches, json);
+                    return Ok(());
+                }
+            };
+            let search_id = searches
+                .iter()
+                .find(|search| search.name == name)
+                .map(|search| search.id);
+
+            if let Some(filter) = filter {
+                let filter = serde_json::from_str::<SearchFilter>(&filter)
+                    .map_err(|err| format!("invalid filter: {err}"))?;
+                let search = match search_id {
+                    Some(search_id) => service.edit_saved_search(search_id, name, filter).await,
+                    None => service.create_saved_search(name, filter).await,
+                }
+                .err_to_str()?;
+                print_id(search.id, json);
+                return Ok(());
+            }
+
+            let search_id = search_id.ok_or(format!("saved search `{name}` not found"))?;
+            if delete {
+                service.delete_saved_search(search_id).await.err_to_str()?;
+                print_id(search_id, json);
+            } else {
+                let rows = service
+                    .evaluate_saved_search(search_id)
+                    .await
+                    .err_to_str()?
+                    .into_iter()
+                    .map(|(id, item)| ItemRow { id, item })
+                    .collect::<Vec<_>>();
+                print_items(&rows, json);
+            }
+        }
+        Command::Tag { item_id, tags } => {
+            for tag in tags {
+                service
+                    .edit_tag(item_id, EditMode::Add, tag)
+                    .await
+                    .err_to_str()?;
+            }
+            print_id(item_id, json);
+        }
+        Command::Untag { item_id, tags } => {
+            for tag in tags {
+                service
+                    .edit_tag(item_id, EditMode::Remove, tag)
+                    .await
+                    .err_to_str()?;
+            }
+            print_id(item_id, json);
+        }
+        Command::Rm { item_id } => {
+            service.delete_todo_item(item_id).await.err_to_str()?;
+            print_id(item_id, json);
+        }
+        Command::Tags { stats: true } => {
+            let rows = service.fetch_tag_stats().await.err_to_str()?;
+            print_tag_stats(&rows, json);
+        }
+        Command::Suggest { prefix, limit } => {
+            let rows = service
+                .suggest_tags(prefix, limit)
+                .await
+                .err_to_str()?
+                .into_iter()
+                .map(|(id, value)| TagRow {
+                    id,
+                    value,
+                    meta: TagMeta::default(),
+                })
+                .collect::<Vec<_>>();
+            print_tags(&rows, json);
+        }
+        Command::Prune { dry_run } => {
+            let rows = service
+                .prune_unused_tags(dry_run)
+                .await
+                .err_to_str()?
+                .into_iter()
+                .map(|(id, value)| TagRow {
+                    id,
+                    value,
+                    meta: TagMeta::default(),
+                })
+                .collect::<Vec<_>>();
+            print_tags(&rows, json);
+        }
+        Command::Tags { stats: false } => {
+            let rows = service
+                .fetch_all_tags()
+                .await
+                .err_to_str()?
+                .into_iter()
+                .map(|(id, value, meta)| TagRow { id, value, meta })
+                .collect::<Vec<_>>();
+            print_tags(&rows, json);
+        }
+        Command::Merge {
+            target_id,
+            source_ids,
+        } => {
+            service
+                .merge_tags(source_ids, target_id)
+                .await
+                .err_to_str()?;
+            print_id(target_id, json);
+        }
+    }
+    Ok(())
+}
+
+fn print_id(id: i32, json: bool) {
+    if json {
+        println!("{}", serde_json::json!({ "id": id }));
+    } else {
+        println!("{id}");
+    }
+}
+
+fn print_items(rows: &[ItemRow], json: bool) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(rows).expect("Unreachable")
+        );
+        return;
+    }
+    let table = rows
+        .iter()
+        .map(|ItemRow { id, item }| {
+            vec![
+                id.to_string(),
+                if item.done { "x" } else { " " }.to_string(),
+                format!("{:?}", item.priority),
+                item.due.map(|due| due.to_string()).unwrap_or_default(),
+                item.message.clone(),
+                item.tags.join(", "),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(
+        &["ID", "DONE", "PRIORITY", "DUE", "MESSAGE", "TAGS"],
+        &table,
+    );
+}
+
+fn print_tags(rows: &[TagRow], json: bool) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(rows).expect("Unreachable")
+        );
+        return;
+    }
+    let table = rows
+        .iter()
+        .map(|TagRow { id, value, meta }| {
+            vec![
+                id.to_string(),
+                meta.icon.clone().unwrap_or_default(),
+                value.clone(),
+                meta.color.clone().unwrap_or_default(),
+                meta.description.clone().unwrap_or_default(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(&["ID", "ICON", "TAG", "COLOR", "DESCRIPTION"], &table);
+}
+
+fn print_tag_stats(rows: &[TagStats], json: bool) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(rows).expect("Unreachable")
+        );
+        return;
+    }
+    let table = rows
+        .iter()
+        .map(|stats| {
+            vec![
+                stats.id.to_string(),
+                stats.value.clone(),
+                stats.open.to_string(),
+                stats.done.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(&["ID", "TAG", "OPEN", "DONE"], &table);
+}
+
+fn print_attachments(rows: &[Attachment], json: bool) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(rows).expect("Unreachable")
+        );
+        return;
+    }
+    let table = rows
+        .iter()
+        .map(|attachment| {
+            vec![
+                attachment.id.to_string(),
+                attachment.name.clone(),
+                attachment.mime.clone(),
+                attachment.size.to_string(),
+                attachment.created_at.to_rfc3339(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(&["ID", "NAME", "MIME", "SIZE", "CREATED"], &table);
+}
+
+fn print_activity(rows: &[Activity], json: bool) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(rows).expect("Unreachable")
+        );
+        return;
+    }
+    let table = rows
+        .iter()
+        .map(|activity| {
+            let what = match activity {
+                Activity::Comment(comment) => {
+                    let edited = if comment.edited { " (edited)" } else { "" };
+                    format!("{}: {}{edited}", comment.author, comment.body)
+                }
+                Activity::PriorityChanged { from, to, .. } => {
+                    format!("priority {from:?} -> {to:?}")
+                }
+                Activity::DoneChanged { done: true, .. } => "done".into(),
+                Activity::DoneChanged { done: false, .. } => "reopened".into(),
+            };
+            vec![activity.at().to_rfc3339(), what]
+        })
+        .collect::<Vec<_>>();
+    print_table(&["AT", "ACTIVITY"], &table);
+}
+
+fn print_saved_searches(searches: &[SavedSearch], json: bool) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(searches).expect("Unreachable")
+        );
+        return;
+    }
+    let table = searches
+        .iter()
+        .map(|search| {
+            vec![
+                search.id.to_string(),
+                search.name.clone(),
+                serde_json::to_string(&search.filter).expect("Unreachable"),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(&["ID", "NAME", "FILTER"], &table);
+}
+
+fn print_stats(stats: &Stats, weekly: bool, json: bool) {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(stats).expect("Unreachable")
+        );
+        return;
+    }
+    let periods = if weekly { &stats.weeks } else { &stats.days };
+    let table = periods
+        .iter()
+        .map(|period| {
+            vec![
+                period.start.to_string(),
+                period.created.to_string(),
+                period.completed.to_string(),
+                period.open.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(&["START", "CREATED", "COMPLETED", "OPEN"], &table);
+    println!();
+
+    let rate = |items: i32, done: i32, rate: Option<f64>| {
+        let rate = rate.map_or_else(|| "-".into(), |rate| format!("{:.0}%", rate * 100.0));
+        vec![items.to_string(), done.to_string(), rate]
+    };
+    let table = stats
+        .by_priority
+        .iter()
+        .map(|by| {
+            [
+                vec![format!("{:?}", by.key)],
+                rate(by.items, by.done, by.rate),
+            ]
+            .concat()
+        })
+        .chain(stats.by_tag.iter().map(|by| {
+            [
+                vec![format!("#{}", by.key)],
+                rate(by.items, by.done, by.rate),
+            ]
+            .concat()
+        }))
+        .collect::<Vec<_>>();
+    print_table(&["GROUP", "CREATED", "DONE", "RATE"], &table);
+    println!();
+
+    if let Some(seconds) = stats.average_seconds_to_complete {
+        println!(
+            "average time to complete: {}h {}m",
+            seconds / 3600,
+            seconds % 3600 / 60
+        );
+    }
+    println!(
+        "streak: {} days, longest {} days",
+        stats.current_streak, stats.longest_streak
+    );
+}
+
+fn print_table(header: &[&str], rows: &[Vec<String>]) {
+    // width of each column is the widest cell in it
+    let widths = header
+        .iter()
+        .enumerate()
+        .map(|(idx, title)| {
+            rows.iter()
+                .map(|row| row[idx].chars().count())
+                .chain([title.len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    let print_row = |cells: Vec<&str>| {
+        let line = cells
+            .into_iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    };
+
+    print_row(header.to_vec());
+    for row in rows {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+}
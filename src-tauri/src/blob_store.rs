@@ -0,0 +1,107 @@
+//! content addressed store of the attached files, a blob is named by the sha256 of its
+//! content, so the same file attached many times is stored once
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    root: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// where the blob is, the first two chars of the hash name a sub dir to keep the
+    /// dirs small
+    pub fn path(&self, hash: &str) -> PathBuf {
+        let (dir, name) = hash.split_at(2.min(hash.len()));
+        self.root.join(dir).join(name)
+    }
+
+    /// copy the file into the store, return the hash and the size of the content
+    pub fn put(&self, source: &Path) -> io::Result<(String, u64)> {
+        fs::create_dir_all(&self.root)?;
+        // write to a temp file first, a blob in the store is always complete
+        let mut temp = NamedTempFile::new_in(&self.root)?;
+        let mut source = File::open(source)?;
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        let mut buf = [0; 8192];
+        loop {
+            let len = source.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            hasher.update(&buf[..len]);
+            temp.write_all(&buf[..len])?;
+            size += len as u64;
+        }
+
+        let hash = format!("{:x}", hasher.finalize());
+        let path = self.path(&hash);
+        if !path.exists() {
+            fs::create_dir_all(path.parent().expect("Unreachable"))?;
+            temp.persist(&path).map_err(|err| err.error)?;
+        }
+        Ok((hash, size))
+    }
+
+    /// copy the blob out of the store
+    pub fn export(&self, hash: &str, target: &Path) -> io::Result<()> {
+        if let Some(dir) = target.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::copy(self.path(hash), target)?;
+        Ok(())
+    }
+
+    /// remove the blob, a blob already removed is fine
+    pub fn remove(&self, hash: &str) -> io::Result<()> {
+        match fs::remove_file(self.path(hash)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_blob_store {
+    use std::fs;
+
+    use super::BlobStore;
+
+    #[test]
+    fn test_put_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::new(dir.path().join("blobs"));
+        let source = dir.path().join("a.txt");
+        fs::write(&source, "abc").unwrap();
+
+        let (hash, size) = store.put(&source).unwrap();
+        assert_eq!(
+            hash,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(size, 3);
+        assert_eq!(fs::read(store.path(&hash)).unwrap(), b"abc");
+        // the same content is stored once
+        assert_eq!(store.put(&source).unwrap(), (hash.clone(), 3));
+
+        let target = dir.path().join("out").join("a.txt");
+        store.export(&hash, &target).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"abc");
+
+        store.remove(&hash).unwrap();
+        assert!(!store.path(&hash).exists());
+        store.remove(&hash).unwrap();
+    }
+}
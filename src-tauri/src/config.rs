@@ -0,0 +1,194 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+
+const CONFIG_PATH: &str = "./config.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub http_api: HttpApiConfig,
+    pub tag: TagPolicy,
+    pub limits: InputLimits,
+    pub attachment: AttachmentConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpApiConfig {
+    /// the http api is off unless enabled explicitly
+    pub enable: bool,
+    pub port: u16,
+    /// every request must carry `Authorization: Bearer <token>`
+    pub token: Option<String>,
+}
+
+impl Default for HttpApiConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            port: 7878,
+            token: None,
+        }
+    }
+}
+
+/// how the tag names are normalized, two names with the same [`TagPolicy::key`]
+/// are the same tag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagPolicy {
+    /// remove the leading and trailing whitespace
+    pub trim: bool,
+    /// unicode normalization form C
+    pub nfc: bool,
+    /// compare ignore case by the unicode full case folding,
+    /// the tag still display in the case it was created
+    pub case_insensitive: bool,
+}
+
+impl Default for TagPolicy {
+    fn default() -> Self {
+        Self {
+            trim: true,
+            nfc: true,
+            case_insensitive: true,
+        }
+    }
+}
+
+impl TagPolicy {
+    /// the name to store and display
+    pub fn normalize(&self, tag: &str) -> String {
+        let tag = if self.trim { tag.trim() } else { tag };
+        if self.nfc {
+            tag.nfc().collect()
+        } else {
+            tag.to_owned()
+        }
+    }
+
+    /// the name to compare with
+    pub fn key(&self, tag: &str) -> String {
+        let tag = self.normalize(tag);
+        if self.case_insensitive {
+            let folded = caseless::default_case_fold_str(&tag);
+            // the folding may break the composition, compose it again
+            if self.nfc {
+                folded.nfc().collect()
+            } else {
+                folded
+            }
+        } else {
+            tag
+        }
+    }
+}
+
+/// the limits of the user input, the lengths count in chars
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputLimits {
+    pub max_message_len: usize,
+    pub max_tag_len: usize,
+    pub max_notes_len: usize,
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        Self {
+            max_message_len: 1024,
+            max_tag_len: 64,
+            max_notes_len: 65536,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttachmentConfig {
+    /// the store of the attached files, `attachments` under the app data directory if not set
+    pub dir: Option<PathBuf>,
+}
+
+impl AttachmentConfig {
+    /// the directory set in the config, or the one under `app_data_dir`. the working
+    /// directory is the last resort when the platform has no app data directory
+    pub fn dir_or(&self, app_data_dir: Option<PathBuf>) -> PathBuf {
+        self.dir
+            .clone()
+            .or_else(|| app_data_dir.map(|dir| dir.join("attachments")))
+            .unwrap_or_else(|| PathBuf::from("./attachments"))
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("read config failure: {0}")]
+    Io(#[from] io::Error),
+    #[error("bad config format: {0}")]
+    Format(#[from] serde_json::Error),
+}
+
+impl AppConfig {
+    /// load the config next to the database, using the default one if the file not exist
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from(CONFIG_PATH)
+    }
+
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        match fs::read(path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_config {
+    use std::path::PathBuf;
+
+    use super::{AttachmentConfig, TagPolicy};
+
+    #[test]
+    fn test_attachment_dir() {
+        let app_data_dir = || Some(PathBuf::from("/data/app"));
+        let config = AttachmentConfig::default();
+        assert_eq!(
+            config.dir_or(app_data_dir()),
+            PathBuf::from("/data/app/attachments")
+        );
+        assert_eq!(config.dir_or(None), PathBuf::from("./attachments"));
+
+        let config = AttachmentConfig {
+            dir: Some("/files".into()),
+        };
+        assert_eq!(config.dir_or(app_data_dir()), PathBuf::from("/files"));
+    }
+
+    #[test]
+    fn test_tag_policy() {
+        let policy = TagPolicy::default();
+        assert_eq!(policy.normalize(" Work "), "Work");
+        assert_eq!(policy.key(" Work "), policy.key("work"));
+        // `e` with a combining acute accent is the same as `é`
+        assert_eq!(policy.key("Cafe\u{301}"), policy.key("café"));
+        // the full case folding, a lowercase would keep `ß` apart from `SS`
+        assert_eq!(policy.key("Straße"), policy.key("STRASSE"));
+        assert_eq!(policy.key("ΣΊΣΥΦΟΣ"), policy.key("σίσυφος"));
+
+        let policy = TagPolicy {
+            trim: false,
+            nfc: false,
+            case_insensitive: false,
+        };
+        assert_ne!(policy.key(" Work "), policy.key("work"));
+        assert_ne!(policy.key("Cafe\u{301}"), policy.key("Café"));
+        assert_ne!(policy.key("Straße"), policy.key("STRASSE"));
+    }
+}
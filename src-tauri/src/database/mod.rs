@@ -1,30 +1,114 @@
 pub mod priority;
-use sea_query::Iden;
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+pub mod rank;
+use std::{str::FromStr, time::Duration};
 
-use self::models::{tag_item_bind::BindEntity, tags::TagEntity, todo_item::TodoItemEntity};
+use sea_query::{ColumnDef, Iden, SqliteQueryBuilder, Table};
+use sqlx::{
+    query, query_as,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteQueryResult},
+    SqlitePool,
+};
+
+use self::models::{
+    attachment::AttachmentEntity, comment::CommentEntity, dependency::DependencyEntity,
+    item_event::ItemEventEntity, saved_search::SavedSearchEntity, tag_item_bind::BindEntity,
+    tags::TagEntity, time_entry::TimeEntryEntity, todo_item::TodoItemEntity,
+};
 
 pub mod models;
 
+pub const DATABASE_URL: &str = r#"sqlite://./app.sqlite?mode=rwc"#;
+
 pub async fn init_sqlite() -> SqlitePool {
+    init_sqlite_with(DATABASE_URL).await
+}
+
+/// open the database at `url` and create the tables if not exist
+///
+/// sqlite connections are opened in WAL mode and wait for the lock held by another process,
+/// so the gui and the cli can share one database file
+pub async fn init_sqlite_with(url: &str) -> SqlitePool {
+    let options = SqliteConnectOptions::from_str(url)
+        .expect("bad sqlite url")
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(Duration::from_secs(5));
     let pool = SqlitePoolOptions::new()
-        .connect(r#"sqlite://./app.sqlite?mode=rwc"#)
+        .connect_with(options)
         .await
         .expect("start sqlite failure");
 
     // init tables
-    TagEntity::create_table(&pool)
-        .await
-        .expect("create table failure");
+    create_tables(&pool).await.expect("create table failure");
+    pool
+}
 
-    TodoItemEntity::create_table(&pool)
-        .await
-        .expect("create table failure");
+pub async fn create_tables(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+    TagEntity::create_table(pool).await?;
+    TodoItemEntity::create_table(pool).await?;
+    BindEntity::create_table(pool).await?;
+    AttachmentEntity::create_table(pool).await?;
+    CommentEntity::create_table(pool).await?;
+    ItemEventEntity::create_table(pool).await?;
+    DependencyEntity::create_table(pool).await?;
+    TimeEntryEntity::create_table(pool).await?;
+    SavedSearchEntity::create_table(pool).await?;
+    Ok(())
+}
 
-    BindEntity::create_table(&pool)
-        .await
-        .expect("create table failure");
-    pool
+/// an update or delete matched no row is reported as [`sqlx::Error::RowNotFound`]
+pub(crate) fn ensure_affected(result: SqliteQueryResult) -> Result<(), sqlx::Error> {
+    if result.rows_affected() == 0 {
+        Err(sqlx::Error::RowNotFound)
+    } else {
+        Ok(())
+    }
+}
+
+/// escape the wildcards of `LIKE` with `\`, the escape char of the [`sea_query::LikeExpr`]
+pub(crate) fn escape_like(text: &str) -> String {
+    text.chars().fold(String::new(), |mut escaped, c| {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+/// add the column to the table created by an older version
+///
+/// sqlite not support `ADD COLUMN IF NOT EXISTS`, so check the exist columns first,
+/// another connection may still add the column between the check and the alter
+pub async fn add_column_if_not_exists<T, C>(
+    pool: &SqlitePool,
+    table: T,
+    column: C,
+    def: impl FnOnce(&mut ColumnDef) -> &mut ColumnDef,
+) -> Result<(), sqlx::Error>
+where
+    T: Iden + 'static,
+    C: Iden + 'static,
+{
+    let name = column.to_string();
+    let columns: Vec<(String,)> = query_as("SELECT name FROM pragma_table_info(?)")
+        .bind(table.to_string())
+        .fetch_all(pool)
+        .await?;
+
+    if columns.iter().all(|(exist,)| exist != &name) {
+        let sql = Table::alter()
+            .table(table)
+            .add_column(def(&mut ColumnDef::new(column)))
+            .build(SqliteQueryBuilder);
+        if let Err(err) = query(&sql).execute(pool).await {
+            let added = matches!(&err, sqlx::Error::Database(db)
+                if db.message().starts_with("duplicate column name"));
+            if !added {
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, Iden)]
@@ -0,0 +1,239 @@
+use chrono::{DateTime, Utc};
+use sea_query::{ColumnDef, Expr, Iden, Order, Query, SqliteQueryBuilder, Table};
+use sea_query_binder::SqlxBinder;
+use sqlx::{query, query_as_with, query_with, Executor, FromRow, Sqlite, SqlitePool};
+
+use crate::database::ensure_affected;
+
+pub struct AttachmentEntity;
+
+#[derive(Debug, Iden)]
+pub enum Attachment {
+    Table,
+    Id,
+    ItemId,
+    /// the file name when it was attached
+    Name,
+    Mime,
+    Size,
+    /// sha256 of the content, the key of the blob in the store
+    Hash,
+    CreatedAt,
+}
+
+impl Attachment {
+    fn get_columns() -> [Self; 7] {
+        [
+            Self::Id,
+            Self::ItemId,
+            Self::Name,
+            Self::Mime,
+            Self::Size,
+            Self::Hash,
+            Self::CreatedAt,
+        ]
+    }
+}
+
+#[derive(Debug, FromRow, PartialEq)]
+pub struct AttachmentModel {
+    pub id: i32,
+    pub item_id: i32,
+    pub name: String,
+    pub mime: String,
+    pub size: i64,
+    pub hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AttachmentEntity {
+    pub async fn create_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let sql = Table::create()
+            .table(Attachment::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(Attachment::Id)
+                    .integer()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(ColumnDef::new(Attachment::ItemId).integer().not_null())
+            .col(ColumnDef::new(Attachment::Name).text().not_null())
+            .col(ColumnDef::new(Attachment::Mime).text().not_null())
+            .col(ColumnDef::new(Attachment::Size).big_integer().not_null())
+            .col(ColumnDef::new(Attachment::Hash).text().not_null())
+            .col(ColumnDef::new(Attachment::CreatedAt).timestamp().not_null())
+            .build(SqliteQueryBuilder);
+
+        query(&sql).execute(pool).await?;
+        Ok(())
+    }
+
+    pub async fn save(
+        pool: &SqlitePool,
+        item_id: i32,
+        name: String,
+        mime: String,
+        size: i64,
+        hash: String,
+    ) -> Result<i32, sqlx::Error> {
+        let (sql, values) = Query::insert()
+            .into_table(Attachment::Table)
+            .columns(Attachment::get_columns().into_iter().skip(1))
+            .values_panic([
+                item_id.into(),
+                name.into(),
+                mime.into(),
+                size.into(),
+                hash.into(),
+                Utc::now().into(),
+            ])
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .map(|result| result.last_insert_rowid() as i32)
+    }
+
+    /// the attachments of the item, the earliest attached first
+    pub async fn fetch_all_by_item_id(
+        pool: &SqlitePool,
+        item_id: i32,
+    ) -> Result<Vec<AttachmentModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(Attachment::get_columns())
+            .from(Attachment::Table)
+            .and_where(Expr::col(Attachment::ItemId).eq(item_id))
+            .order_by(Attachment::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    pub async fn find_by_id(pool: &SqlitePool, id: i32) -> Result<AttachmentModel, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(Attachment::get_columns())
+            .from(Attachment::Table)
+            .and_where(Expr::col(Attachment::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_one(pool).await
+    }
+
+    /// the hashes of all the blobs still in use
+    pub async fn fetch_all_hash(pool: &SqlitePool) -> Result<Vec<String>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .distinct()
+            .column(Attachment::Hash)
+            .from(Attachment::Table)
+            .build_sqlx(SqliteQueryBuilder);
+
+        let hashes: Vec<(String,)> = query_as_with(&sql, values).fetch_all(pool).await?;
+        Ok(hashes.into_iter().map(|(hash,)| hash).collect())
+    }
+
+    /// the hashes of the blobs used by the attachments of the items
+    pub async fn fetch_all_hash_by_item_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_ids: impl IntoIterator<Item = i32>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .distinct()
+            .column(Attachment::Hash)
+            .from(Attachment::Table)
+            .and_where(Expr::col(Attachment::ItemId).is_in(item_ids))
+            .build_sqlx(SqliteQueryBuilder);
+
+        let hashes: Vec<(String,)> = query_as_with(&sql, values).fetch_all(executor).await?;
+        Ok(hashes.into_iter().map(|(hash,)| hash).collect())
+    }
+
+    pub async fn remove(pool: &SqlitePool, id: i32) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(Attachment::Table)
+            .and_where(Expr::col(Attachment::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    /// remove the attachments of the items, the blobs are left for the garbage collection
+    pub async fn remove_all_by_item_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(Attachment::Table)
+            .and_where(Expr::col(Attachment::ItemId).is_in(item_ids))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_attachment {
+    use crate::database::models::test_sqlite::{init, item};
+
+    use super::AttachmentEntity;
+
+    #[tokio::test]
+    async fn test_attachment() {
+        let pool = init().await;
+        let foo = item("Foo").save(&pool).await;
+        let bar = item("Bar").save(&pool).await;
+        let save = |item_id: i32, name: &str, hash: &str| {
+            AttachmentEntity::save(
+                &pool,
+                item_id,
+                name.into(),
+                "text/plain".into(),
+                3,
+                hash.into(),
+            )
+        };
+        let a = save(foo, "a.txt", "aaa").await.unwrap();
+        save(foo, "copy.txt", "aaa").await.unwrap();
+        save(bar, "b.txt", "bbb").await.unwrap();
+
+        let names = AttachmentEntity::fetch_all_by_item_id(&pool, foo)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|model| model.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a.txt", "copy.txt"]);
+        assert_eq!(
+            AttachmentEntity::find_by_id(&pool, a).await.unwrap().hash,
+            "aaa"
+        );
+
+        // the same content is only referenced once
+        let mut hashes = AttachmentEntity::fetch_all_hash(&pool).await.unwrap();
+        hashes.sort();
+        assert_eq!(hashes, ["aaa", "bbb"]);
+
+        assert_eq!(
+            AttachmentEntity::fetch_all_hash_by_item_id(&pool, [foo])
+                .await
+                .unwrap(),
+            ["aaa"]
+        );
+        AttachmentEntity::remove_all_by_item_id(&pool, [foo])
+            .await
+            .unwrap();
+        assert_eq!(
+            AttachmentEntity::fetch_all_hash(&pool).await.unwrap(),
+            ["bbb"]
+        );
+        assert!(matches!(
+            AttachmentEntity::remove(&pool, a).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+    }
+}
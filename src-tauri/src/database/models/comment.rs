@@ -0,0 +1,214 @@
+use chrono::{DateTime, Utc};
+use sea_query::{ColumnDef, Expr, Iden, Order, Query, SqliteQueryBuilder, Table};
+use sea_query_binder::SqlxBinder;
+use sqlx::{query, query_as_with, query_with, Executor, FromRow, Sqlite, SqlitePool};
+
+use crate::database::ensure_affected;
+
+pub struct CommentEntity;
+
+#[derive(Debug, Iden)]
+pub enum Comment {
+    Table,
+    Id,
+    ItemId,
+    /// who wrote it, a free label as there is no user account
+    Author,
+    Body,
+    CreatedAt,
+    /// the body is changed after created
+    Edited,
+}
+
+impl Comment {
+    fn get_columns() -> [Self; 6] {
+        [
+            Self::Id,
+            Self::ItemId,
+            Self::Author,
+            Self::Body,
+            Self::CreatedAt,
+            Self::Edited,
+        ]
+    }
+}
+
+#[derive(Debug, FromRow, PartialEq)]
+pub struct CommentModel {
+    pub id: i32,
+    pub item_id: i32,
+    pub author: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub edited: bool,
+}
+
+impl CommentEntity {
+    pub async fn create_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let sql = Table::create()
+            .table(Comment::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(Comment::Id)
+                    .integer()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(ColumnDef::new(Comment::ItemId).integer().not_null())
+            .col(ColumnDef::new(Comment::Author).text().not_null())
+            .col(ColumnDef::new(Comment::Body).text().not_null())
+            .col(ColumnDef::new(Comment::CreatedAt).timestamp().not_null())
+            .col(
+                ColumnDef::new(Comment::Edited)
+                    .boolean()
+                    .not_null()
+                    .default(false),
+            )
+            .build(SqliteQueryBuilder);
+
+        query(&sql).execute(pool).await?;
+        Ok(())
+    }
+
+    pub async fn save(
+        pool: &SqlitePool,
+        item_id: i32,
+        author: String,
+        body: String,
+    ) -> Result<i32, sqlx::Error> {
+        let (sql, values) = Query::insert()
+            .into_table(Comment::Table)
+            .columns([
+                Comment::ItemId,
+                Comment::Author,
+                Comment::Body,
+                Comment::CreatedAt,
+            ])
+            .values_panic([
+                item_id.into(),
+                author.into(),
+                body.into(),
+                Utc::now().into(),
+            ])
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .map(|result| result.last_insert_rowid() as i32)
+    }
+
+    pub async fn find_by_id(pool: &SqlitePool, id: i32) -> Result<CommentModel, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(Comment::get_columns())
+            .from(Comment::Table)
+            .and_where(Expr::col(Comment::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_one(pool).await
+    }
+
+    /// the comments on the item, the earliest first
+    pub async fn fetch_all_by_item_id(
+        pool: &SqlitePool,
+        item_id: i32,
+    ) -> Result<Vec<CommentModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(Comment::get_columns())
+            .from(Comment::Table)
+            .and_where(Expr::col(Comment::ItemId).eq(item_id))
+            .order_by(Comment::CreatedAt, Order::Asc)
+            .order_by(Comment::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    /// change the body and mark the comment edited
+    pub async fn update_body(pool: &SqlitePool, id: i32, body: String) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(Comment::Table)
+            .values([(Comment::Body, body.into()), (Comment::Edited, true.into())])
+            .and_where(Expr::col(Comment::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    pub async fn remove(pool: &SqlitePool, id: i32) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(Comment::Table)
+            .and_where(Expr::col(Comment::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    pub async fn remove_all_by_item_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(Comment::Table)
+            .and_where(Expr::col(Comment::ItemId).is_in(item_ids))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_comment {
+    use crate::database::models::test_sqlite::{init, item};
+
+    use super::CommentEntity;
+
+    #[tokio::test]
+    async fn test_comment() {
+        let pool = init().await;
+        let foo = item("Foo").save(&pool).await;
+        let a = CommentEntity::save(&pool, foo, "amy".into(), "first".into())
+            .await
+            .unwrap();
+        CommentEntity::save(&pool, foo, "bob".into(), "second".into())
+            .await
+            .unwrap();
+
+        CommentEntity::update_body(&pool, a, "first!".into())
+            .await
+            .unwrap();
+        let comments = CommentEntity::fetch_all_by_item_id(&pool, foo)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|comment| (comment.author, comment.body, comment.edited))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            comments,
+            [
+                ("amy".into(), "first!".into(), true),
+                ("bob".into(), "second".into(), false)
+            ]
+        );
+
+        CommentEntity::remove(&pool, a).await.unwrap();
+        assert!(matches!(
+            CommentEntity::find_by_id(&pool, a).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        CommentEntity::remove_all_by_item_id(&pool, [foo])
+            .await
+            .unwrap();
+        assert!(CommentEntity::fetch_all_by_item_id(&pool, foo)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+use chrono::Utc;
+use sea_query::{ColumnDef, Cond, Expr, Iden, Index, OnConflict, Query, SqliteQueryBuilder, Table};
+use sea_query_binder::SqlxBinder;
+use sqlx::{query, query_as_with, query_with, Executor, FromRow, Sqlite, SqlitePool};
+
+use crate::database::{ensure_affected, models::todo_item::TodoItem};
+
+pub struct DependencyEntity;
+
+/// the item can not be done before the blocker is done
+#[derive(Debug, Iden)]
+pub enum Dependency {
+    Table,
+    ItemId,
+    BlockerId,
+    CreatedAt,
+}
+
+#[derive(Debug, Clone, Copy, FromRow, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DependencyModel {
+    pub item_id: i32,
+    pub blocker_id: i32,
+}
+
+impl DependencyEntity {
+    pub async fn create_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let sql = Table::create()
+            .table(Dependency::Table)
+            .if_not_exists()
+            .col(ColumnDef::new(Dependency::ItemId).integer().not_null())
+            .col(ColumnDef::new(Dependency::BlockerId).integer().not_null())
+            .col(ColumnDef::new(Dependency::CreatedAt).timestamp().not_null())
+            .primary_key(
+                Index::create()
+                    .col(Dependency::ItemId)
+                    .col(Dependency::BlockerId),
+            )
+            .build(SqliteQueryBuilder);
+
+        query(&sql).execute(pool).await?;
+        Ok(())
+    }
+
+    /// add the dependency, adding it again changes nothing
+    pub async fn save<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        DependencyModel {
+            item_id,
+            blocker_id,
+        }: DependencyModel,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::insert()
+            .into_table(Dependency::Table)
+            .columns([
+                Dependency::ItemId,
+                Dependency::BlockerId,
+                Dependency::CreatedAt,
+            ])
+            .values_panic([item_id.into(), blocker_id.into(), Utc::now().into()])
+            .on_conflict(
+                OnConflict::columns([Dependency::ItemId, Dependency::BlockerId])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+
+    pub async fn fetch_all<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+    ) -> Result<Vec<DependencyModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns([Dependency::ItemId, Dependency::BlockerId])
+            .from(Dependency::Table)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(executor).await
+    }
+
+    pub async fn fetch_all_blocker_id(
+        pool: &SqlitePool,
+        item_id: i32,
+    ) -> Result<Vec<i32>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .column(Dependency::BlockerId)
+            .from(Dependency::Table)
+            .and_where(Expr::col(Dependency::ItemId).eq(item_id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        let ids: Vec<(i32,)> = query_as_with(&sql, values).fetch_all(pool).await?;
+        Ok(ids.into_iter().map(|(id,)| id).collect())
+    }
+
+    /// the dependencies whose blocker is not done yet, of the items or of all the items
+    /// when `item_ids` is none
+    pub async fn fetch_all_open<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_ids: Option<Vec<i32>>,
+    ) -> Result<Vec<DependencyModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .column((Dependency::Table, Dependency::ItemId))
+            .column((Dependency::Table, Dependency::BlockerId))
+            .from(Dependency::Table)
+            .inner_join(
+                TodoItem::Table,
+                Expr::tbl(Dependency::Table, Dependency::BlockerId)
+                    .equals(TodoItem::Table, TodoItem::Id),
+            )
+            .and_where(Expr::col((TodoItem::Table, TodoItem::Done)).eq(false))
+            .and_where_option(
+                item_ids.map(|ids| Expr::col((Dependency::Table, Dependency::ItemId)).is_in(ids)),
+            )
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(executor).await
+    }
+
+    pub async fn remove(
+        pool: &SqlitePool,
+        DependencyModel {
+            item_id,
+            blocker_id,
+        }: DependencyModel,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(Dependency::Table)
+            .and_where(Expr::col(Dependency::ItemId).eq(item_id))
+            .and_where(Expr::col(Dependency::BlockerId).eq(blocker_id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    /// remove the dependencies on either side of the items
+    pub async fn remove_all_by_item_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), sqlx::Error> {
+        let item_ids = item_ids.into_iter().collect::<Vec<_>>();
+        let (sql, values) = Query::delete()
+            .from_table(Dependency::Table)
+            .cond_where(
+                Cond::any()
+                    .add(Expr::col(Dependency::ItemId).is_in(item_ids.iter().copied()))
+                    .add(Expr::col(Dependency::BlockerId).is_in(item_ids)),
+            )
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_dependency {
+    use crate::database::models::test_sqlite::{init, item};
+
+    use super::{DependencyEntity, DependencyModel};
+
+    #[tokio::test]
+    async fn test_dependency() {
+        let pool = init().await;
+        let foo = item("Foo").save(&pool).await;
+        let bar = item("Bar").save(&pool).await;
+        let baz = item("Baz").done().save(&pool).await;
+        let edge = |item_id: i32, blocker_id: i32| DependencyModel {
+            item_id,
+            blocker_id,
+        };
+
+        for dependency in [
+            edge(foo, bar),
+            edge(foo, baz),
+            edge(bar, baz),
+            edge(foo, bar),
+        ] {
+            DependencyEntity::save(&pool, dependency).await.unwrap();
+        }
+        let mut all = DependencyEntity::fetch_all(&pool).await.unwrap();
+        all.sort();
+        assert_eq!(all, [edge(foo, bar), edge(foo, baz), edge(bar, baz)]);
+        assert_eq!(
+            DependencyEntity::fetch_all_blocker_id(&pool, bar)
+                .await
+                .unwrap(),
+            [baz]
+        );
+
+        // baz is done, only bar still blocks
+        assert_eq!(
+            DependencyEntity::fetch_all_open(&pool, None).await.unwrap(),
+            [edge(foo, bar)]
+        );
+        assert!(DependencyEntity::fetch_all_open(&pool, Some(vec![bar]))
+            .await
+            .unwrap()
+            .is_empty());
+
+        DependencyEntity::remove(&pool, edge(foo, baz))
+            .await
+            .unwrap();
+        assert!(matches!(
+            DependencyEntity::remove(&pool, edge(foo, baz)).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        DependencyEntity::remove_all_by_item_id(&pool, [bar])
+            .await
+            .unwrap();
+        assert!(DependencyEntity::fetch_all(&pool).await.unwrap().is_empty());
+    }
+}
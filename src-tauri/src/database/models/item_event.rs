@@ -0,0 +1,199 @@
+use chrono::{DateTime, Utc};
+use sea_query::{ColumnDef, Expr, Iden, Order, Query, SqliteQueryBuilder, Table, Value};
+use sea_query_binder::SqlxBinder;
+use sqlx::{query, query_as_with, query_with, Executor, FromRow, Sqlite, SqlitePool};
+use tap::Pipe;
+
+use crate::database::priority::Priority;
+
+pub struct ItemEventEntity;
+
+/// the history of the changes on the items, a row has either the priority columns or
+/// the done column set
+#[derive(Debug, Iden)]
+pub enum ItemEvent {
+    Table,
+    Id,
+    ItemId,
+    OldPriority,
+    Priority,
+    Done,
+    HappenedAt,
+}
+
+impl ItemEvent {
+    fn get_columns() -> [Self; 6] {
+        [
+            Self::Id,
+            Self::ItemId,
+            Self::OldPriority,
+            Self::Priority,
+            Self::Done,
+            Self::HappenedAt,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemChange {
+    Priority { from: Priority, to: Priority },
+    Done(bool),
+}
+
+#[derive(Debug, FromRow, PartialEq)]
+pub struct ItemEventModel {
+    pub id: i32,
+    pub item_id: i32,
+    pub old_priority: Option<Priority>,
+    pub priority: Option<Priority>,
+    pub done: Option<bool>,
+    pub happened_at: DateTime<Utc>,
+}
+
+impl ItemEventModel {
+    pub fn change(&self) -> Option<ItemChange> {
+        match (self.old_priority, self.priority, self.done) {
+            (Some(from), Some(to), _) => Some(ItemChange::Priority { from, to }),
+            (_, _, Some(done)) => Some(ItemChange::Done(done)),
+            _ => None,
+        }
+    }
+}
+
+impl ItemEventEntity {
+    pub async fn create_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let sql = Table::create()
+            .table(ItemEvent::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(ItemEvent::Id)
+                    .integer()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(ColumnDef::new(ItemEvent::ItemId).integer().not_null())
+            .col(ColumnDef::new(ItemEvent::OldPriority).small_integer())
+            .col(ColumnDef::new(ItemEvent::Priority).small_integer())
+            .col(ColumnDef::new(ItemEvent::Done).boolean())
+            .col(ColumnDef::new(ItemEvent::HappenedAt).timestamp().not_null())
+            .build(SqliteQueryBuilder);
+
+        query(&sql).execute(pool).await?;
+        Ok(())
+    }
+
+    /// record the changes of the items, all happened now
+    pub async fn save_all<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        changes: impl IntoIterator<Item = (i32, ItemChange)>,
+    ) -> Result<(), sqlx::Error> {
+        let mut changes = changes.into_iter().peekable();
+        // insert without any values is not a valid statement
+        if changes.peek().is_none() {
+            return Ok(());
+        }
+        let (sql, values) = Query::insert()
+            .into_table(ItemEvent::Table)
+            .columns(ItemEvent::get_columns().into_iter().skip(1))
+            .pipe(|query| {
+                let now = Utc::now();
+                changes.for_each(|(item_id, change)| {
+                    let (old_priority, priority, done) = match change {
+                        ItemChange::Priority { from, to } => {
+                            (from.into(), to.into(), Value::Bool(None))
+                        }
+                        ItemChange::Done(done) => (
+                            Value::TinyUnsigned(None),
+                            Value::TinyUnsigned(None),
+                            done.into(),
+                        ),
+                    };
+                    query.values_panic([item_id.into(), old_priority, priority, done, now.into()]);
+                });
+                query
+            })
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+
+    /// the history of the item, the earliest first
+    pub async fn fetch_all_by_item_id(
+        pool: &SqlitePool,
+        item_id: i32,
+    ) -> Result<Vec<ItemEventModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(ItemEvent::get_columns())
+            .from(ItemEvent::Table)
+            .and_where(Expr::col(ItemEvent::ItemId).eq(item_id))
+            .order_by(ItemEvent::HappenedAt, Order::Asc)
+            .order_by(ItemEvent::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    pub async fn remove_all_by_item_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(ItemEvent::Table)
+            .and_where(Expr::col(ItemEvent::ItemId).is_in(item_ids))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_item_event {
+    use crate::database::{
+        models::test_sqlite::{init, item},
+        priority::Priority,
+    };
+
+    use super::{ItemChange, ItemEventEntity};
+
+    #[tokio::test]
+    async fn test_item_event() {
+        let pool = init().await;
+        let foo = item("Foo").save(&pool).await;
+        let bar = item("Bar").save(&pool).await;
+        let raise = ItemChange::Priority {
+            from: Priority::Medium,
+            to: Priority::High,
+        };
+        ItemEventEntity::save_all(&pool, [(foo, raise), (bar, ItemChange::Done(true))])
+            .await
+            .unwrap();
+        ItemEventEntity::save_all(&pool, [(foo, ItemChange::Done(true))])
+            .await
+            .unwrap();
+
+        let changes = ItemEventEntity::fetch_all_by_item_id(&pool, foo)
+            .await
+            .unwrap()
+            .iter()
+            .map(|event| event.change())
+            .collect::<Vec<_>>();
+        assert_eq!(changes, [Some(raise), Some(ItemChange::Done(true))]);
+
+        ItemEventEntity::remove_all_by_item_id(&pool, [foo])
+            .await
+            .unwrap();
+        assert!(ItemEventEntity::fetch_all_by_item_id(&pool, foo)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            ItemEventEntity::fetch_all_by_item_id(&pool, bar)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -1,20 +1,98 @@
+pub mod attachment;
+pub mod comment;
+pub mod dependency;
+pub mod item_event;
+pub mod saved_search;
 pub mod tag_item_bind;
 pub mod tags;
+pub mod time_entry;
 pub mod todo_item;
 
 #[cfg(test)]
 pub mod test_sqlite {
-    use once_cell::sync::OnceCell;
-    use sqlx::SqlitePool;
+    use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
 
-    use crate::database::init_sqlite;
+    use crate::{
+        config::TagPolicy,
+        database::{create_tables, priority::Priority},
+    };
 
-    pub static SQLITE: OnceCell<SqlitePool> = OnceCell::new();
+    use super::{
+        tag_item_bind::{BindEntity, BindModel},
+        tags::TagEntity,
+        todo_item::TodoItemEntity,
+    };
 
-    pub async fn init() {
-        if SQLITE.get().is_none() {
-            let pool = init_sqlite().await;
-            SQLITE.set(pool).expect("Unreachable");
+    /// a new empty in memory database with all tables created
+    ///
+    /// every connection to `sqlite::memory:` owns its own database,
+    /// so the pool keep exactly one connection alive
+    pub async fn init() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect("sqlite::memory:")
+            .await
+            .expect("start sqlite failure");
+        create_tables(&pool).await.expect("create table failure");
+        pool
+    }
+
+    pub fn item(message: &str) -> ItemFixture {
+        ItemFixture {
+            message: message.into(),
+            priority: Priority::Medium,
+            done: false,
+            tags: vec![],
+        }
+    }
+
+    pub struct ItemFixture {
+        message: String,
+        priority: Priority,
+        done: bool,
+        tags: Vec<String>,
+    }
+
+    impl ItemFixture {
+        pub fn priority(mut self, priority: Priority) -> Self {
+            self.priority = priority;
+            self
+        }
+
+        pub fn done(mut self) -> Self {
+            self.done = true;
+            self
         }
+
+        pub fn tag(mut self, tag: &str) -> Self {
+            self.tags.push(tag.into());
+            self
+        }
+
+        /// save the item, its tags and the binds, return the id of the item
+        pub async fn save(self, pool: &SqlitePool) -> i32 {
+            let item_id = TodoItemEntity::save(pool, self.message, self.priority, self.done)
+                .await
+                .expect("save item fixture failure");
+            for tag in self.tags {
+                let tag_id = self::tag(pool, &tag).await;
+                bind(pool, tag_id, item_id).await;
+            }
+            item_id
+        }
+    }
+
+    pub async fn tag(pool: &SqlitePool, value: &str) -> i32 {
+        TagEntity::save(pool, &TagPolicy::default(), value)
+            .await
+            .expect("save tag fixture failure")
+    }
+
+    pub async fn bind(pool: &SqlitePool, tag_id: i32, item_id: i32) {
+        BindEntity::save_all(pool, [BindModel::new(tag_id, item_id)])
+            .await
+            .expect("save bind fixture failure")
     }
 }
@@ -0,0 +1,199 @@
+use chrono::Utc;
+use sea_query::{ColumnDef, Expr, Iden, Order, Query, SqliteQueryBuilder, Table};
+use sea_query_binder::SqlxBinder;
+use sqlx::{query, query_as_with, query_with, FromRow, SqlitePool};
+
+use crate::database::ensure_affected;
+
+pub struct SavedSearchEntity;
+
+/// a named filter on the items, the filter is kept as json the service understands
+#[derive(Debug, Iden)]
+pub enum SavedSearch {
+    Table,
+    Id,
+    Name,
+    Filter,
+    CreatedAt,
+}
+
+impl SavedSearch {
+    fn get_columns() -> [Self; 3] {
+        [Self::Id, Self::Name, Self::Filter]
+    }
+}
+
+#[derive(Debug, FromRow, PartialEq)]
+pub struct SavedSearchModel {
+    pub id: i32,
+    pub name: String,
+    pub filter: String,
+}
+
+impl SavedSearchEntity {
+    pub async fn create_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let sql = Table::create()
+            .table(SavedSearch::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(SavedSearch::Id)
+                    .integer()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(ColumnDef::new(SavedSearch::Name).text().not_null())
+            .col(ColumnDef::new(SavedSearch::Filter).text().not_null())
+            .col(
+                ColumnDef::new(SavedSearch::CreatedAt)
+                    .timestamp()
+                    .not_null(),
+            )
+            .build(SqliteQueryBuilder);
+
+        query(&sql).execute(pool).await?;
+        Ok(())
+    }
+
+    pub async fn save(pool: &SqlitePool, name: String, filter: String) -> Result<i32, sqlx::Error> {
+        let (sql, values) = Query::insert()
+            .into_table(SavedSearch::Table)
+            .columns([
+                SavedSearch::Name,
+                SavedSearch::Filter,
+                SavedSearch::CreatedAt,
+            ])
+            .values_panic([name.into(), filter.into(), Utc::now().into()])
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .map(|result| result.last_insert_rowid() as i32)
+    }
+
+    pub async fn find_by_id(pool: &SqlitePool, id: i32) -> Result<SavedSearchModel, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(SavedSearch::get_columns())
+            .from(SavedSearch::Table)
+            .and_where(Expr::col(SavedSearch::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_one(pool).await
+    }
+
+    pub async fn find_by_name(
+        pool: &SqlitePool,
+        name: &str,
+    ) -> Result<Option<SavedSearchModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(SavedSearch::get_columns())
+            .from(SavedSearch::Table)
+            .and_where(Expr::col(SavedSearch::Name).eq(name))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_optional(pool).await
+    }
+
+    /// all the saved searches in the order of the name
+    pub async fn fetch_all(pool: &SqlitePool) -> Result<Vec<SavedSearchModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(SavedSearch::get_columns())
+            .from(SavedSearch::Table)
+            .order_by(SavedSearch::Name, Order::Asc)
+            .order_by(SavedSearch::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    pub async fn update(
+        pool: &SqlitePool,
+        SavedSearchModel { id, name, filter }: SavedSearchModel,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(SavedSearch::Table)
+            .values([
+                (SavedSearch::Name, name.into()),
+                (SavedSearch::Filter, filter.into()),
+            ])
+            .and_where(Expr::col(SavedSearch::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    pub async fn remove(pool: &SqlitePool, id: i32) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(SavedSearch::Table)
+            .and_where(Expr::col(SavedSearch::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
+    }
+}
+
+#[cfg(test)]
+mod test_saved_search {
+    use crate::database::models::test_sqlite::init;
+
+    use super::{SavedSearchEntity, SavedSearchModel};
+
+    #[tokio::test]
+    async fn test_saved_search() {
+        let pool = init().await;
+        let foo = SavedSearchEntity::save(&pool, "Foo".into(), "{}".into())
+            .await
+            .unwrap();
+        let bar = SavedSearchEntity::save(&pool, "Bar".into(), "[]".into())
+            .await
+            .unwrap();
+        let names = |models: Vec<SavedSearchModel>| {
+            models
+                .into_iter()
+                .map(|model| model.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            names(SavedSearchEntity::fetch_all(&pool).await.unwrap()),
+            ["Bar", "Foo"]
+        );
+        assert_eq!(
+            SavedSearchEntity::find_by_name(&pool, "Foo")
+                .await
+                .unwrap()
+                .map(|model| model.id),
+            Some(foo)
+        );
+
+        let baz = SavedSearchModel {
+            id: bar,
+            name: "Baz".into(),
+            filter: "null".into(),
+        };
+        SavedSearchEntity::update(&pool, baz).await.unwrap();
+        assert_eq!(
+            SavedSearchEntity::find_by_id(&pool, bar).await.unwrap(),
+            SavedSearchModel {
+                id: bar,
+                name: "Baz".into(),
+                filter: "null".into(),
+            }
+        );
+
+        SavedSearchEntity::remove(&pool, foo).await.unwrap();
+        assert!(matches!(
+            SavedSearchEntity::remove(&pool, foo).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        assert!(SavedSearchEntity::find_by_name(&pool, "Foo")
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -1,8 +1,15 @@
-use sea_query::{ColumnDef, Condition, Expr, Iden, Query, SqliteQueryBuilder, Table};
+use chrono::{NaiveDate, Utc};
+use sea_query::{
+    ColumnDef, Condition, Expr, Iden, Index, OnConflict, Query, SqliteQueryBuilder, Table,
+};
 use sea_query_binder::SqlxBinder;
-use sqlx::{query, query_as_with, query_with, FromRow, SqlitePool};
+use sqlx::{query, query_as_with, query_with, Executor, FromRow, Sqlite, SqlitePool};
 use tap::Pipe;
 
+use crate::database::{add_column_if_not_exists, Count};
+
+use super::todo_item::{TodoItem, TodoItemEntity};
+
 pub struct BindEntity;
 
 #[derive(Debug, Iden)]
@@ -10,6 +17,8 @@ pub enum TagItemBind {
     Table,
     TagId,
     ItemId,
+    /// when the tag is bind with the item, binds created by older version have none
+    BindAt,
 }
 #[derive(Debug, FromRow)]
 pub struct BindModel {
@@ -17,6 +26,35 @@ pub struct BindModel {
     pub item_id: i32,
 }
 
+/// the number of the items bind with the tag in done or undone state
+#[derive(Debug, FromRow, PartialEq)]
+pub struct BindCount {
+    pub tag_id: i32,
+    pub done: bool,
+    pub count: i32,
+}
+
+/// the estimates of the items bind with the tag in done or undone state, see
+/// [`TodoItemEntity::select_estimate_sum`]
+#[derive(Debug, FromRow, PartialEq)]
+pub struct BindEstimateSum {
+    pub tag_id: i32,
+    pub done: bool,
+    pub items: i32,
+    pub estimated: i32,
+    pub minutes: Option<i64>,
+    pub points: Option<i64>,
+}
+
+/// the items bind with the tag created in a range, and how many of them are done, see
+/// [`TodoItemEntity::select_done_count`]
+#[derive(Debug, FromRow, PartialEq)]
+pub struct BindDoneCount {
+    pub tag_id: i32,
+    pub items: i32,
+    pub done: i32,
+}
+
 impl BindModel {
     pub fn new(tag_id: i32, item_id: i32) -> Self {
         Self { tag_id, item_id }
@@ -28,30 +66,108 @@ impl BindEntity {
         let sql = Table::create()
             .table(TagItemBind::Table)
             .if_not_exists()
-            .col(ColumnDef::new(TagItemBind::TagId).integer().primary_key())
-            .col(ColumnDef::new(TagItemBind::ItemId).integer().primary_key())
+            .col(ColumnDef::new(TagItemBind::TagId).integer().not_null())
+            .col(ColumnDef::new(TagItemBind::ItemId).integer().not_null())
+            .col(ColumnDef::new(TagItemBind::BindAt).timestamp())
+            .primary_key(
+                Index::create()
+                    .col(TagItemBind::TagId)
+                    .col(TagItemBind::ItemId),
+            )
             .build(SqliteQueryBuilder);
 
         query(&sql).execute(pool).await?;
+        add_column_if_not_exists(pool, TagItemBind::Table, TagItemBind::BindAt, |col| {
+            col.timestamp()
+        })
+        .await?;
         Ok(())
     }
-    pub async fn save_all<I: IntoIterator<Item = BindModel>>(
-        pool: &SqlitePool,
-        peers: I,
+    /// save the binds, the binds already exist are skipped
+    pub async fn save_all<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        peers: impl IntoIterator<Item = BindModel>,
     ) -> Result<(), sqlx::Error> {
+        let mut peers = peers.into_iter().peekable();
+        // insert without any values is not a valid statement
+        if peers.peek().is_none() {
+            return Ok(());
+        }
         let (sql, values) = Query::insert()
             .into_table(TagItemBind::Table)
-            .columns([TagItemBind::TagId, TagItemBind::ItemId])
+            .columns([TagItemBind::TagId, TagItemBind::ItemId, TagItemBind::BindAt])
             .pipe(|query| {
-                peers.into_iter().for_each(|BindModel { tag_id, item_id }| {
-                    query.values_panic([tag_id.into(), item_id.into()]);
+                let now = Utc::now();
+                peers.for_each(|BindModel { tag_id, item_id }| {
+                    query.values_panic([tag_id.into(), item_id.into(), now.into()]);
                 });
                 query
             })
+            .on_conflict(
+                OnConflict::columns([TagItemBind::TagId, TagItemBind::ItemId])
+                    .do_nothing()
+                    .to_owned(),
+            )
             .build_sqlx(SqliteQueryBuilder);
-        query_with(&sql, values).execute(pool).await?;
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+    /// bind the tag with all the items, the items already bind with it are skipped
+    pub async fn bind_items<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        tag_id: i32,
+        item_ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), sqlx::Error> {
+        let mut item_ids = item_ids.into_iter().peekable();
+        if item_ids.peek().is_none() {
+            return Ok(());
+        }
+        let (sql, values) = Query::insert()
+            .into_table(TagItemBind::Table)
+            .columns([TagItemBind::TagId, TagItemBind::ItemId, TagItemBind::BindAt])
+            .pipe(|query| {
+                let now = Utc::now();
+                item_ids.for_each(|item_id| {
+                    query.values_panic([tag_id.into(), item_id.into(), now.into()]);
+                });
+                query
+            })
+            .on_conflict(
+                OnConflict::columns([TagItemBind::TagId, TagItemBind::ItemId])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+
+    pub async fn unbind_items<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        tag_id: i32,
+        item_ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(TagItemBind::Table)
+            .and_where(Expr::col(TagItemBind::TagId).eq(tag_id))
+            .and_where(Expr::col(TagItemBind::ItemId).is_in(item_ids))
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+
+    pub async fn remove_bind_item_ids<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(TagItemBind::Table)
+            .and_where(Expr::col(TagItemBind::ItemId).is_in(item_ids))
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(executor).await?;
         Ok(())
     }
+
     pub async fn fetch_all(pool: &SqlitePool) -> Result<Vec<BindModel>, sqlx::Error> {
         let (sql, values) = Query::select()
             .columns([TagItemBind::TagId, TagItemBind::ItemId])
@@ -61,6 +177,75 @@ impl BindEntity {
         query_as_with(&sql, values).fetch_all(pool).await
     }
 
+    /// count the bind items of every tag group by the done state,
+    /// the tags without any bind are not included
+    pub async fn count_by_done(pool: &SqlitePool) -> Result<Vec<BindCount>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .column((TagItemBind::Table, TagItemBind::TagId))
+            .column((TodoItem::Table, TodoItem::Done))
+            .expr_as(
+                Expr::col((TagItemBind::Table, TagItemBind::ItemId)).count(),
+                Count,
+            )
+            .from(TagItemBind::Table)
+            .inner_join(
+                TodoItem::Table,
+                Expr::tbl(TagItemBind::Table, TagItemBind::ItemId)
+                    .equals(TodoItem::Table, TodoItem::Id),
+            )
+            .group_by_col((TagItemBind::Table, TagItemBind::TagId))
+            .group_by_col((TodoItem::Table, TodoItem::Done))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    /// sum the estimates of the bind items of every tag group by the done state,
+    /// the tags without any bind are not included
+    pub async fn sum_estimate_by_done(
+        pool: &SqlitePool,
+    ) -> Result<Vec<BindEstimateSum>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .column((TagItemBind::Table, TagItemBind::TagId))
+            .column((TodoItem::Table, TodoItem::Done))
+            .pipe(TodoItemEntity::select_estimate_sum)
+            .from(TagItemBind::Table)
+            .inner_join(
+                TodoItem::Table,
+                Expr::tbl(TagItemBind::Table, TagItemBind::ItemId)
+                    .equals(TodoItem::Table, TodoItem::Id),
+            )
+            .group_by_col((TagItemBind::Table, TagItemBind::TagId))
+            .group_by_col((TodoItem::Table, TodoItem::Done))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    /// count the items created in the range and the done ones by the tag, the tags
+    /// without any item are not included
+    pub async fn count_done_by_tag(
+        pool: &SqlitePool,
+        utc_offset: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<BindDoneCount>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .column((TagItemBind::Table, TagItemBind::TagId))
+            .pipe(TodoItemEntity::select_done_count)
+            .from(TagItemBind::Table)
+            .inner_join(
+                TodoItem::Table,
+                Expr::tbl(TagItemBind::Table, TagItemBind::ItemId)
+                    .equals(TodoItem::Table, TodoItem::Id),
+            )
+            .and_where(TodoItemEntity::created_between(utc_offset, from, to))
+            .group_by_col((TagItemBind::Table, TagItemBind::TagId))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
     pub async fn fetch_all_tag_id(
         pool: &SqlitePool,
         item_id: i32,
@@ -108,13 +293,16 @@ impl BindEntity {
         Ok(())
     }
 
-    pub async fn remove_bind_tag_id(pool: &SqlitePool, tag_id: i32) -> Result<(), sqlx::Error> {
+    pub async fn remove_bind_tag_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        tag_id: i32,
+    ) -> Result<(), sqlx::Error> {
         let (sql, values) = Query::delete()
             .from_table(TagItemBind::Table)
             .and_where(Expr::col(TagItemBind::TagId).eq(tag_id))
             .build_sqlx(SqliteQueryBuilder);
 
-        query_with(&sql, values).execute(pool).await?;
+        query_with(&sql, values).execute(executor).await?;
         Ok(())
     }
     pub async fn remove_bind_item_id(pool: &SqlitePool, item_id: i32) -> Result<(), sqlx::Error> {
@@ -127,3 +315,79 @@ impl BindEntity {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test_bind {
+
+    use crate::database::models::test_sqlite::{bind, init, item, tag};
+
+    use super::{BindCount, BindEntity};
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let pool = init().await;
+        let foo = item("Foo").tag("a").tag("b").save(&pool).await;
+        let bar = item("Bar").save(&pool).await;
+        let a = tag(&pool, "a").await;
+        bind(&pool, a, bar).await;
+
+        assert_eq!(
+            BindEntity::fetch_all_item_id(&pool, a).await.unwrap(),
+            [foo, bar]
+        );
+        assert_eq!(
+            BindEntity::fetch_all_tag_id(&pool, foo)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        BindEntity::remove_bind_tag_id(&pool, a).await.unwrap();
+        assert_eq!(
+            BindEntity::fetch_all_tag_id(&pool, foo)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(BindEntity::fetch_all_tag_id(&pool, bar)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_count_by_done() {
+        let pool = init().await;
+        item("Foo").tag("a").tag("b").save(&pool).await;
+        item("Bar").tag("a").save(&pool).await;
+        item("Baz").tag("a").done().save(&pool).await;
+        let a = tag(&pool, "a").await;
+        let b = tag(&pool, "b").await;
+        tag(&pool, "unused").await;
+
+        let mut counts = BindEntity::count_by_done(&pool).await.unwrap();
+        counts.sort_by_key(|count| (count.tag_id, count.done));
+        assert_eq!(
+            counts,
+            [
+                BindCount {
+                    tag_id: a,
+                    done: false,
+                    count: 2
+                },
+                BindCount {
+                    tag_id: a,
+                    done: true,
+                    count: 1
+                },
+                BindCount {
+                    tag_id: b,
+                    done: false,
+                    count: 1
+                },
+            ]
+        );
+    }
+}
@@ -1,8 +1,22 @@
-use sea_query::{ColumnDef, ColumnType, Expr, Iden, Query, SqliteQueryBuilder, Table};
-use sqlx::{query, query_as_with, query_with, FromRow, SqlitePool};
+use std::collections::HashMap;
+
+use sea_query::{
+    ColumnDef, ColumnType, Expr, Func, Iden, LikeExpr, OnConflict, Order, Query, SelectStatement,
+    SimpleExpr, SqliteQueryBuilder, SubQueryStatement, Table,
+};
+use sqlx::{
+    query, query_as_with, query_with, Executor, FromRow, Sqlite, SqliteConnection, SqlitePool,
+};
 use tap::Pipe;
 
-use sea_query_binder::{SqlxBinder, SqlxValues};
+use sea_query_binder::SqlxBinder;
+
+use crate::{
+    config::TagPolicy,
+    database::{add_column_if_not_exists, ensure_affected, escape_like},
+};
+
+use super::tag_item_bind::TagItemBind;
 pub struct TagEntity;
 
 #[derive(Debug, Iden)]
@@ -10,12 +24,66 @@ enum Tag {
     Table,
     Id,
     Value,
+    ParentId,
+    Color,
+    Icon,
+    Description,
+    Position,
+    /// the value normalized by the [`TagPolicy`], tags are looked up by it
+    NormalizedValue,
+}
+
+impl Tag {
+    fn get_columns() -> [Self; 7] {
+        [
+            Self::Id,
+            Self::Value,
+            Self::ParentId,
+            Self::Color,
+            Self::Icon,
+            Self::Description,
+            Self::Position,
+        ]
+    }
 }
 
 #[derive(Debug, FromRow)]
 pub struct TagModel {
     pub id: i32,
     pub value: String,
+    pub parent_id: Option<i32>,
+    pub color: Option<String>,
+    pub icon: Option<String>,
+    pub description: Option<String>,
+    /// manual sort position, tags are fetched in this order
+    pub position: i32,
+}
+
+/// tags share the same normalized value, created before the policy applied
+#[derive(Debug, PartialEq, Eq)]
+pub struct TagCollision {
+    pub key: String,
+    pub tags: Vec<(i32, String)>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TagNameError {
+    #[error("Tag `{0}` already exists")]
+    Exists(String),
+    #[error("Tag {0} not found")]
+    NotFound(i32),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TagTreeError {
+    #[error("Move tag {tag_id} under tag {parent_id} will make a cycle")]
+    Cycle { tag_id: i32, parent_id: i32 },
+    #[error("Tag {0} not found")]
+    NotFound(i32),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
 }
 
 impl TagEntity {
@@ -33,19 +101,107 @@ impl TagEntity {
                     .not_null()
                     .unique_key(),
             )
+            .col(&mut ColumnDef::new_with_type(
+                Tag::ParentId,
+                ColumnType::Integer(None),
+            ))
+            .col(&mut ColumnDef::new_with_type(Tag::Color, ColumnType::Text))
+            .col(&mut ColumnDef::new_with_type(Tag::Icon, ColumnType::Text))
+            .col(&mut ColumnDef::new_with_type(
+                Tag::Description,
+                ColumnType::Text,
+            ))
+            .col(
+                ColumnDef::new_with_type(Tag::Position, ColumnType::Integer(None))
+                    .not_null()
+                    .default(0),
+            )
+            .col(&mut ColumnDef::new_with_type(
+                Tag::NormalizedValue,
+                ColumnType::Text,
+            ))
             .build(SqliteQueryBuilder);
 
         query(&stat).execute(pool).await?;
+        // the tag table created by older version miss the new columns
+        add_column_if_not_exists(pool, Tag::Table, Tag::ParentId, |col| col.integer()).await?;
+        add_column_if_not_exists(pool, Tag::Table, Tag::Color, |col| col.text()).await?;
+        add_column_if_not_exists(pool, Tag::Table, Tag::Icon, |col| col.text()).await?;
+        add_column_if_not_exists(pool, Tag::Table, Tag::Description, |col| col.text()).await?;
+        add_column_if_not_exists(pool, Tag::Table, Tag::Position, |col| {
+            col.integer().not_null().default(0)
+        })
+        .await?;
+        add_column_if_not_exists(pool, Tag::Table, Tag::NormalizedValue, |col| col.text()).await?;
         Ok(())
     }
 
-    fn query_by_tag(tag: &impl AsRef<str>) -> (String, SqlxValues) {
+    /// fill the normalized value of every tag follow the policy,
+    /// return the tags which become the same tag under the policy
+    ///
+    /// the colliding tags are kept, the oldest one is found by name,
+    /// the others can be merged into it
+    pub async fn normalize(
+        pool: &SqlitePool,
+        policy: &TagPolicy,
+    ) -> Result<Vec<TagCollision>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let (sql, values) = Query::select()
+            .columns([Tag::Id, Tag::Value, Tag::NormalizedValue])
+            .from(Tag::Table)
+            .order_by(Tag::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+        let tags = query_as_with::<_, (i32, String, Option<String>), _>(&sql, values)
+            .fetch_all(&mut tx)
+            .await?;
+
+        let mut groups = HashMap::<String, Vec<(i32, String)>>::new();
+        for (id, value, normalized) in tags {
+            let key = policy.key(&value);
+            // only the tags created before or under another policy need update
+            if normalized.as_ref() != Some(&key) {
+                let (sql, values) = Query::update()
+                    .table(Tag::Table)
+                    .value(Tag::NormalizedValue, key.as_str().into())
+                    .and_where(Expr::col(Tag::Id).eq(id))
+                    .build_sqlx(SqliteQueryBuilder);
+                query_with(&sql, values).execute(&mut tx).await?;
+            }
+            groups.entry(key).or_default().push((id, value));
+        }
+        tx.commit().await?;
+
+        let mut collisions = groups
+            .into_iter()
+            .filter(|(_, tags)| tags.len() > 1)
+            .map(|(key, tags)| TagCollision { key, tags })
+            .collect::<Vec<_>>();
+        collisions.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(collisions)
+    }
+
+    fn query_by_tag(policy: &TagPolicy, tag: &impl AsRef<str>) -> SelectStatement {
+        // the oldest tag wins when some tags collide
         Query::select()
             .columns([Tag::Id])
             .from(Tag::Table)
-            .and_where(Expr::col(Tag::Value).eq(tag.as_ref()))
+            .and_where(Expr::col(Tag::NormalizedValue).eq(policy.key(tag.as_ref())))
+            .order_by(Tag::Id, Order::Asc)
+            .limit(1)
             .to_owned()
-            .build_sqlx(SqliteQueryBuilder)
+    }
+
+    /// the id of the tag under the policy, `None` if not exist
+    pub async fn find_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        policy: &TagPolicy,
+        tag: &impl AsRef<str>,
+    ) -> Result<Option<i32>, sqlx::Error> {
+        let (sql, values) = Self::query_by_tag(policy, tag).build_sqlx(SqliteQueryBuilder);
+        query_as_with(&sql, values)
+            .fetch_optional(executor)
+            .await
+            .map(|v| v.map(|(id,)| id))
     }
 
     pub async fn fetch_all(
@@ -53,8 +209,10 @@ impl TagEntity {
         limit: Option<u64>,
     ) -> Result<Vec<TagModel>, sqlx::Error> {
         let (sql, values) = Query::select()
-            .columns([Tag::Id, Tag::Value])
+            .columns(Tag::get_columns())
             .from(Tag::Table)
+            .order_by(Tag::Position, Order::Asc)
+            .order_by(Tag::Id, Order::Asc)
             .pipe(|q| {
                 if let Some(limit) = limit {
                     q.limit(limit)
@@ -72,7 +230,7 @@ impl TagEntity {
         ids: impl IntoIterator<Item = i32>,
     ) -> Result<Vec<TagModel>, sqlx::Error> {
         let (sql, values) = Query::select()
-            .columns([Tag::Id, Tag::Value])
+            .columns(Tag::get_columns())
             .from(Tag::Table)
             .cond_where(Expr::col(Tag::Id).is_in(ids))
             .build_sqlx(SqliteQueryBuilder);
@@ -80,60 +238,430 @@ impl TagEntity {
         query_as_with(&sql, values).fetch_all(pool).await
     }
 
-    pub async fn remove(pool: &SqlitePool, tag: &impl AsRef<str>) -> Result<(), sqlx::Error> {
+    pub async fn remove<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        policy: &TagPolicy,
+        tag: &impl AsRef<str>,
+    ) -> Result<(), sqlx::Error> {
         let (sql, values) = Query::delete()
             .from_table(Tag::Table)
-            .and_where(Expr::col(Tag::Value).eq(tag.as_ref()))
+            .and_where(Expr::col(Tag::Id).in_subquery(Self::query_by_tag(policy, tag)))
             .build_sqlx(SqliteQueryBuilder);
 
-        query_with(&sql, values).execute(pool).await.map(|_| ())
+        query_with(&sql, values)
+            .execute(executor)
+            .await
+            .and_then(ensure_affected)
     }
 
-    pub async fn get_id(pool: &SqlitePool, tag: &impl AsRef<str>) -> Result<i32, sqlx::Error> {
-        let (sql, values) = Self::query_by_tag(tag);
-        query_as_with(&sql, values)
-            .fetch_one(pool)
-            .await
-            .map(|(resp,)| resp)
+    pub async fn get_id(
+        pool: &SqlitePool,
+        policy: &TagPolicy,
+        tag: &impl AsRef<str>,
+    ) -> Result<i32, sqlx::Error> {
+        Self::find_id(pool, policy, tag)
+            .await?
+            .ok_or(sqlx::Error::RowNotFound)
     }
 
+    /// rename the tag, the new name can not be the same as another tag under the policy
     pub async fn edit(
         pool: &SqlitePool,
+        policy: &TagPolicy,
         id: i32,
         tag: &impl AsRef<str>,
+    ) -> Result<(), TagNameError> {
+        let value = policy.normalize(tag.as_ref());
+        if matches!(Self::find_id(pool, policy, &value).await?, Some(exist) if exist != id) {
+            return Err(TagNameError::Exists(value));
+        }
+
+        let (sql, values) = Query::update()
+            .table(Tag::Table)
+            .values([
+                (Tag::Value, value.as_str().into()),
+                (Tag::NormalizedValue, policy.key(&value).into()),
+            ])
+            .and_where(Expr::col(Tag::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+        if query_with(&sql, values)
+            .execute(pool)
+            .await?
+            .rows_affected()
+            == 0
+        {
+            return Err(TagNameError::NotFound(id));
+        }
+        Ok(())
+    }
+
+    pub async fn update_meta(
+        pool: &SqlitePool,
+        id: i32,
+        color: Option<String>,
+        icon: Option<String>,
+        description: Option<String>,
     ) -> Result<(), sqlx::Error> {
         let (sql, values) = Query::update()
             .table(Tag::Table)
-            .value(Tag::Value, tag.as_ref().into())
+            .values([
+                (Tag::Color, color.into()),
+                (Tag::Icon, icon.into()),
+                (Tag::Description, description.into()),
+            ])
             .and_where(Expr::col(Tag::Id).eq(id))
             .build_sqlx(SqliteQueryBuilder);
-        query_with(&sql, values).execute(pool).await.map(|_| ())
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    /// set the position of the tags follow the order of `ids`
+    pub async fn reorder(
+        pool: &SqlitePool,
+        ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), TagTreeError> {
+        let ids = ids.into_iter().collect::<Vec<_>>();
+        let mut tx = pool.begin().await?;
+        Self::ensure_exist(&mut tx, ids.iter().copied()).await?;
+        for (position, id) in ids.into_iter().enumerate() {
+            let (sql, values) = Query::update()
+                .table(Tag::Table)
+                .value(Tag::Position, (position as i32).into())
+                .and_where(Expr::col(Tag::Id).eq(id))
+                .build_sqlx(SqliteQueryBuilder);
+            query_with(&sql, values).execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// move the tag under the parent, or to the top level when parent is `None`
+    ///
+    /// the parent can not be the tag itself or any descendant of it
+    pub async fn set_parent(
+        pool: &SqlitePool,
+        id: i32,
+        parent_id: Option<i32>,
+    ) -> Result<(), TagTreeError> {
+        let mut tx = pool.begin().await?;
+        Self::ensure_exist(&mut tx, [id].into_iter().chain(parent_id)).await?;
+
+        // walk up from the new parent, the tag must not be found on the way
+        let mut ancestor = parent_id;
+        while let Some(current) = ancestor {
+            if current == id {
+                return Err(TagTreeError::Cycle {
+                    tag_id: id,
+                    parent_id: parent_id.unwrap_or(current),
+                });
+            }
+            let (sql, values) = Query::select()
+                .column(Tag::ParentId)
+                .from(Tag::Table)
+                .and_where(Expr::col(Tag::Id).eq(current))
+                .build_sqlx(SqliteQueryBuilder);
+            ancestor = query_as_with::<_, (Option<i32>,), _>(&sql, values)
+                .fetch_one(&mut tx)
+                .await?
+                .0;
+        }
+
+        let (sql, values) = Query::update()
+            .table(Tag::Table)
+            .value(Tag::ParentId, parent_id.into())
+            .and_where(Expr::col(Tag::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(&mut tx).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// move all the children of the tag to a new parent
+    pub async fn move_children<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+        parent_id: Option<i32>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(Tag::Table)
+            .value(Tag::ParentId, parent_id.into())
+            .and_where(Expr::col(Tag::ParentId).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(executor).await.map(|_| ())
+    }
+
+    /// all the descendants of the tag, not including the tag itself
+    pub async fn fetch_descendants(pool: &SqlitePool, id: i32) -> Result<Vec<i32>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns([Tag::Id, Tag::ParentId])
+            .from(Tag::Table)
+            .and_where(Expr::col(Tag::ParentId).is_not_null())
+            .build_sqlx(SqliteQueryBuilder);
+        let children = query_as_with::<_, (i32, i32), _>(&sql, values)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .fold(HashMap::<i32, Vec<i32>>::new(), |mut map, (id, parent)| {
+                map.entry(parent).or_default().push(id);
+                map
+            });
+
+        let mut descendants = Vec::new();
+        let mut stack = vec![id];
+        while let Some(current) = stack.pop() {
+            for &child in children.get(&current).into_iter().flatten() {
+                descendants.push(child);
+                stack.push(child);
+            }
+        }
+        Ok(descendants)
     }
 
-    pub async fn save(pool: &SqlitePool, tag: impl AsRef<str>) -> Result<i32, sqlx::Error> {
+    /// merge the source tags into the target tag in one transaction
+    ///
+    /// the binds of the sources move to the target, an item already bind with the target
+    /// keep only one bind. the children of the sources move under the target, then the
+    /// sources are removed. nothing changes if the target or any source not exist
+    pub async fn merge(
+        pool: &SqlitePool,
+        source_ids: impl IntoIterator<Item = i32>,
+        target_id: i32,
+    ) -> Result<(), TagTreeError> {
+        let source_ids = source_ids
+            .into_iter()
+            .filter(|id| *id != target_id)
+            .collect::<Vec<_>>();
+        let mut tx = pool.begin().await?;
+        Self::ensure_exist(&mut tx, [target_id].iter().chain(&source_ids).copied()).await?;
+        if source_ids.is_empty() {
+            return Ok(());
+        }
+
+        // copy the binds to the target, skip the items already bind with it
+        let (sql, values) = Query::insert()
+            .into_table(TagItemBind::Table)
+            .columns([TagItemBind::TagId, TagItemBind::ItemId, TagItemBind::BindAt])
+            .select_from(
+                Query::select()
+                    .expr(Expr::val(target_id))
+                    .column(TagItemBind::ItemId)
+                    .column(TagItemBind::BindAt)
+                    .from(TagItemBind::Table)
+                    .and_where(Expr::col(TagItemBind::TagId).is_in(source_ids.clone()))
+                    .to_owned(),
+            )
+            .expect("Unreachable")
+            .on_conflict(
+                OnConflict::columns([TagItemBind::TagId, TagItemBind::ItemId])
+                    .do_nothing()
+                    .to_owned(),
+            )
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(&mut tx).await?;
+
+        let (sql, values) = Query::delete()
+            .from_table(TagItemBind::Table)
+            .and_where(Expr::col(TagItemBind::TagId).is_in(source_ids.clone()))
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(&mut tx).await?;
+
+        // the target may be a descendant of a source, it climbs to the
+        // nearest ancestor which is not removed
+        let (sql, values) = Query::select()
+            .columns([Tag::Id, Tag::ParentId])
+            .from(Tag::Table)
+            .build_sqlx(SqliteQueryBuilder);
+        let parents = query_as_with::<_, (i32, Option<i32>), _>(&sql, values)
+            .fetch_all(&mut tx)
+            .await?
+            .into_iter()
+            .collect::<HashMap<_, _>>();
+        let mut target_parent = parents.get(&target_id).copied().flatten();
+        while let Some(parent) = target_parent.filter(|id| source_ids.contains(id)) {
+            target_parent = parents.get(&parent).copied().flatten();
+        }
+
+        let (sql, values) = Query::update()
+            .table(Tag::Table)
+            .value(Tag::ParentId, target_parent.into())
+            .and_where(Expr::col(Tag::Id).eq(target_id))
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(&mut tx).await?;
+
+        let (sql, values) = Query::update()
+            .table(Tag::Table)
+            .value(Tag::ParentId, target_id.into())
+            .and_where(Expr::col(Tag::ParentId).is_in(source_ids.clone()))
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(&mut tx).await?;
+
+        let (sql, values) = Query::delete()
+            .from_table(Tag::Table)
+            .and_where(Expr::col(Tag::Id).is_in(source_ids))
+            .build_sqlx(SqliteQueryBuilder);
+        query_with(&sql, values).execute(&mut tx).await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// every tag exists, or the first one not exist is reported
+    async fn ensure_exist(
+        conn: &mut SqliteConnection,
+        ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), TagTreeError> {
+        let ids = ids.into_iter().collect::<Vec<_>>();
+        let (sql, values) = Query::select()
+            .column(Tag::Id)
+            .from(Tag::Table)
+            .and_where(Expr::col(Tag::Id).is_in(ids.clone()))
+            .build_sqlx(SqliteQueryBuilder);
+        let exist = query_as_with::<_, (i32,), _>(&sql, values)
+            .fetch_all(conn)
+            .await?;
+        match ids.into_iter().find(|id| !exist.contains(&(*id,))) {
+            Some(id) => Err(TagTreeError::NotFound(id)),
+            None => Ok(()),
+        }
+    }
+
+    /// the tags match the input, the tags start with it come first, then the tags contain
+    /// its chars in order, like `wk` for `work`. in each group the tags bind with more items
+    /// and bind more recently come first
+    pub async fn suggest(
+        pool: &SqlitePool,
+        policy: &TagPolicy,
+        input: &str,
+        limit: u64,
+    ) -> Result<Vec<TagModel>, sqlx::Error> {
+        let key = policy.key(input);
+        let prefix = format!("{}%", escape_like(&key));
+        let fuzzy = key.chars().fold("%".to_owned(), |mut pattern, c| {
+            pattern.push_str(&escape_like(&c.to_string()));
+            pattern.push('%');
+            pattern
+        });
+
+        let (sql, values) = Query::select()
+            .columns(Tag::get_columns().map(|col| (Tag::Table, col)))
+            .from(Tag::Table)
+            .left_join(
+                TagItemBind::Table,
+                Expr::tbl(TagItemBind::Table, TagItemBind::TagId).equals(Tag::Table, Tag::Id),
+            )
+            .and_where(
+                Expr::col((Tag::Table, Tag::NormalizedValue))
+                    .like(LikeExpr::str(&fuzzy).escape('\\')),
+            )
+            .group_by_col((Tag::Table, Tag::Id))
+            .order_by_expr(
+                Expr::col((Tag::Table, Tag::NormalizedValue))
+                    .like(LikeExpr::str(&prefix).escape('\\')),
+                Order::Desc,
+            )
+            .order_by_expr(
+                Expr::col((TagItemBind::Table, TagItemBind::ItemId)).count(),
+                Order::Desc,
+            )
+            .order_by_expr(
+                Expr::col((TagItemBind::Table, TagItemBind::BindAt)).max(),
+                Order::Desc,
+            )
+            .order_by((Tag::Table, Tag::Value), Order::Asc)
+            .limit(limit)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    /// remove the tags not bind with any item, return the removed tags.
+    /// a tag still has children is kept for it groups the children
+    ///
+    /// with `dry_run` nothing is removed, only the tags would be removed are returned
+    pub async fn prune_unused(
+        pool: &SqlitePool,
+        dry_run: bool,
+    ) -> Result<Vec<TagModel>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let (sql, values) = Query::select()
+            .columns(Tag::get_columns())
+            .from(Tag::Table)
+            .and_where(
+                Expr::col(Tag::Id).not_in_subquery(
+                    Query::select()
+                        .column(TagItemBind::TagId)
+                        .from(TagItemBind::Table)
+                        .to_owned(),
+                ),
+            )
+            .and_where(
+                Expr::col(Tag::Id).not_in_subquery(
+                    Query::select()
+                        .column(Tag::ParentId)
+                        .from(Tag::Table)
+                        .and_where(Expr::col(Tag::ParentId).is_not_null())
+                        .to_owned(),
+                ),
+            )
+            .order_by(Tag::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+        let unused = query_as_with::<_, TagModel, _>(&sql, values)
+            .fetch_all(&mut tx)
+            .await?;
+
+        if !dry_run && !unused.is_empty() {
+            let (sql, values) = Query::delete()
+                .from_table(Tag::Table)
+                .and_where(Expr::col(Tag::Id).is_in(unused.iter().map(|tag| tag.id)))
+                .build_sqlx(SqliteQueryBuilder);
+            query_with(&sql, values).execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(unused)
+    }
+
+    /// save the tag if not exist, return the id of the tag
+    pub async fn save(
+        pool: &SqlitePool,
+        policy: &TagPolicy,
+        tag: impl AsRef<str>,
+    ) -> Result<i32, sqlx::Error> {
+        Self::save_in(&mut *pool.acquire().await?, policy, tag).await
+    }
+
+    /// [`TagEntity::save`] on the connection, like a transaction
+    pub async fn save_in(
+        conn: &mut SqliteConnection,
+        policy: &TagPolicy,
+        tag: impl AsRef<str>,
+    ) -> Result<i32, sqlx::Error> {
         // search is the tag exist
-        let (query, values) = Self::query_by_tag(&tag);
-
-        // the tag not exist insert it
-        if let Some((id,)) =
-            query_as_with(&query, values)
-                .fetch_one(pool)
-                .await
-                .pipe(|v| match v {
-                    Ok(v) => Ok(Some(v)),
-                    Err(sqlx::Error::RowNotFound) => Ok(None),
-                    Err(err) => Err(err),
-                })?
-        {
+        if let Some(id) = Self::find_id(&mut *conn, policy, &tag).await? {
             Ok(id)
         } else {
+            // the tag not exist insert it, keep the case it is typed in
+            // new tag is placed after all the exist tags
+            let position = Query::select()
+                .expr(Func::if_null(Expr::col(Tag::Position).max(), Expr::val(-1)))
+                .from(Tag::Table)
+                .to_owned();
             let (query, values) = Query::insert()
                 .into_table(Tag::Table)
-                .columns([Tag::Value])
-                .values_panic([(tag.as_ref()).into()])
+                .columns([Tag::Value, Tag::NormalizedValue, Tag::Position])
+                .exprs_panic([
+                    Expr::value(policy.normalize(tag.as_ref())),
+                    Expr::value(policy.key(tag.as_ref())),
+                    Expr::expr(SimpleExpr::SubQuery(Box::new(
+                        SubQueryStatement::SelectStatement(position),
+                    )))
+                    .add(1),
+                ])
                 .build_sqlx(SqliteQueryBuilder);
 
-            let v = query_with(&query, values).execute(pool).await?;
+            let v = query_with(&query, values).execute(conn).await?;
             Ok(v.last_insert_rowid() as i32)
         }
     }
@@ -142,32 +670,234 @@ impl TagEntity {
 #[cfg(test)]
 mod test_tag {
 
-    use crate::database::models::test_sqlite::{init, SQLITE};
+    use crate::database::models::{
+        tag_item_bind::BindEntity,
+        test_sqlite::{bind, init, item, tag},
+    };
+
+    use crate::config::TagPolicy;
 
-    use super::{TagEntity, TagModel};
+    use super::{TagCollision, TagEntity, TagNameError};
 
     #[tokio::test]
     async fn test_insert() {
-        init().await;
-        let data = TagModel {
-            id: 1,
-            value: "abccc".to_string(),
-        };
+        let pool = init().await;
+
+        let policy = TagPolicy::default();
 
-        let v = TagEntity::save(SQLITE.get().unwrap(), &data.value)
+        let id = TagEntity::save(&pool, &policy, "abccc")
             .await
             .expect("Failure save data");
+        // save an exist tag return the same id
+        let same = TagEntity::save(&pool, &policy, " ABccc ").await.unwrap();
+
+        assert_eq!(id, same);
+        assert_eq!(
+            TagEntity::get_id(&pool, &policy, &"Abccc").await.unwrap(),
+            id
+        );
 
-        println!("{v}")
+        // the display case is the case it is created
+        let id = TagEntity::save(&pool, &policy, " Work ").await.unwrap();
+        assert_eq!(
+            TagEntity::get_id(&pool, &policy, &"work").await.unwrap(),
+            id
+        );
+        let r = TagEntity::find_all_by_id(&pool, [id]).await.unwrap();
+        assert_eq!(r[0].value, "Work");
+
+        TagEntity::remove(&pool, &policy, &"WORK").await.unwrap();
+        assert!(TagEntity::get_id(&pool, &policy, &"Work").await.is_err());
     }
 
     #[tokio::test]
     async fn test_edit() {
-        init().await;
-        let pool = SQLITE.get().unwrap();
+        let pool = init().await;
+        let id = tag(&pool, "abccc").await;
+
+        let policy = TagPolicy::default();
+
+        TagEntity::edit(&pool, &policy, id, &"ccb").await.unwrap();
+        let r = TagEntity::fetch_all(&pool, None).await.unwrap();
+
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].id, id);
+        assert_eq!(r[0].value, "ccb");
+
+        // change the case of the tag itself is fine
+        TagEntity::edit(&pool, &policy, id, &"CCB").await.unwrap();
+        assert_eq!(TagEntity::get_id(&pool, &policy, &"ccb").await.unwrap(), id);
+        // but not the name of another tag
+        let other = tag(&pool, "other").await;
+        assert!(matches!(
+            TagEntity::edit(&pool, &policy, other, &" ccb ").await,
+            Err(TagNameError::Exists(name)) if name == "ccb"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_normalize() {
+        let pool = init().await;
+        let exact = TagPolicy {
+            trim: false,
+            nfc: false,
+            case_insensitive: false,
+        };
+        let upper = TagEntity::save(&pool, &exact, "Work").await.unwrap();
+        let lower = TagEntity::save(&pool, &exact, "work").await.unwrap();
+        let home = TagEntity::save(&pool, &exact, "home").await.unwrap();
+
+        let collisions = TagEntity::normalize(&pool, &TagPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            collisions,
+            [TagCollision {
+                key: "work".into(),
+                tags: vec![(upper, "Work".into()), (lower, "work".into())],
+            }]
+        );
+        // the oldest one is found
+        let policy = TagPolicy::default();
+        assert_eq!(
+            TagEntity::get_id(&pool, &policy, &"WORK").await.unwrap(),
+            upper
+        );
+        assert_eq!(
+            TagEntity::get_id(&pool, &policy, &"Home").await.unwrap(),
+            home
+        );
+
+        // no collision after merge
+        TagEntity::merge(&pool, [lower], upper).await.unwrap();
+        assert!(TagEntity::normalize(&pool, &policy)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_meta_and_order() {
+        let pool = init().await;
+        let a = tag(&pool, "a").await;
+        let b = tag(&pool, "b").await;
+        let c = tag(&pool, "c").await;
+
+        TagEntity::update_meta(
+            &pool,
+            b,
+            Some("#ff0000".into()),
+            Some("🐛".into()),
+            Some("bugs".into()),
+        )
+        .await
+        .unwrap();
+        TagEntity::reorder(&pool, [c, a]).await.unwrap();
+
+        let r = TagEntity::fetch_all(&pool, None).await.unwrap();
+        // `b` keep the position it got when created
+        assert_eq!(r.iter().map(|t| t.id).collect::<Vec<_>>(), [c, a, b]);
+        assert_eq!(r[2].color.as_deref(), Some("#ff0000"));
+        assert_eq!(r[2].icon.as_deref(), Some("🐛"));
+        assert_eq!(r[2].description.as_deref(), Some("bugs"));
+
+        // new tag is placed at the end
+        let d = tag(&pool, "d").await;
+        let r = TagEntity::fetch_all(&pool, None).await.unwrap();
+        assert_eq!(r.last().map(|t| t.id), Some(d));
+    }
+
+    #[tokio::test]
+    async fn test_merge() {
+        let pool = init().await;
+        let foo = item("Foo").tag("bug").tag("defect").save(&pool).await;
+        let bar = item("Bar").tag("bugs").save(&pool).await;
+        let policy = TagPolicy::default();
+        let bug = TagEntity::get_id(&pool, &policy, &"bug").await.unwrap();
+        let defect = TagEntity::get_id(&pool, &policy, &"defect").await.unwrap();
+        let bugs = TagEntity::get_id(&pool, &policy, &"bugs").await.unwrap();
+        let ui = tag(&pool, "ui").await;
+        bind(&pool, ui, bar).await;
+        TagEntity::set_parent(&pool, ui, Some(bugs)).await.unwrap();
+        // merge into a child of a source
+        TagEntity::set_parent(&pool, bug, Some(defect))
+            .await
+            .unwrap();
+
+        TagEntity::merge(&pool, [defect, bugs, bug], bug)
+            .await
+            .unwrap();
+
+        let tags = TagEntity::fetch_all(&pool, None).await.unwrap();
+        assert_eq!(
+            tags.iter().map(|t| (t.id, t.parent_id)).collect::<Vec<_>>(),
+            [(bug, None), (ui, Some(bug))]
+        );
+        assert_eq!(
+            BindEntity::fetch_all_item_id(&pool, bug).await.unwrap(),
+            [foo, bar]
+        );
+        let mut tag_ids = BindEntity::fetch_all_tag_id(&pool, bar).await.unwrap();
+        tag_ids.sort_unstable();
+        assert_eq!(tag_ids, [bug, ui]);
+    }
+
+    #[tokio::test]
+    async fn test_prune_unused() {
+        let pool = init().await;
+        item("Foo").tag("used").save(&pool).await;
+        let unused = tag(&pool, "unused").await;
+        let group = tag(&pool, "group").await;
+        let child = tag(&pool, "child").await;
+        TagEntity::set_parent(&pool, child, Some(group))
+            .await
+            .unwrap();
 
-        TagEntity::edit(pool, 1, &"ccb").await.unwrap();
-        let r = TagEntity::fetch_all(pool, None).await.unwrap();
-        println!("{r:?}")
+        let ids = |tags: Vec<super::TagModel>| tags.into_iter().map(|t| t.id).collect::<Vec<_>>();
+        // the parent of a tag is kept
+        let dry = TagEntity::prune_unused(&pool, true).await.unwrap();
+        assert_eq!(ids(dry), [unused, child]);
+        assert_eq!(TagEntity::fetch_all(&pool, None).await.unwrap().len(), 4);
+
+        let removed = TagEntity::prune_unused(&pool, false).await.unwrap();
+        assert_eq!(ids(removed), [unused, child]);
+        let left = TagEntity::fetch_all(&pool, None).await.unwrap();
+        assert_eq!(
+            left.into_iter().map(|t| t.value).collect::<Vec<_>>(),
+            ["used", "group"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_suggest() {
+        let pool = init().await;
+        let policy = TagPolicy::default();
+        let work = tag(&pool, "Work").await;
+        let walk = tag(&pool, "walk").await;
+        let network = tag(&pool, "network").await;
+        let home = tag(&pool, "home").await;
+        item("Foo").tag("network").save(&pool).await;
+        item("Bar").tag("network").tag("walk").save(&pool).await;
+
+        let suggest = |input: &'static str, limit| {
+            let pool = pool.clone();
+            let policy = policy.clone();
+            async move {
+                TagEntity::suggest(&pool, &policy, input, limit)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|t| t.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        // prefix match first, then the fuzzy match
+        assert_eq!(suggest("w", 10).await, [walk, work, network]);
+        // no prefix match, the most used first
+        assert_eq!(suggest("WK", 10).await, [network, walk, work]);
+        assert_eq!(suggest("wo", 10).await, [work, network]);
+        assert_eq!(suggest("", 2).await, [network, walk]);
+        assert!(suggest("%", 10).await.is_empty());
+        assert_eq!(suggest("h", 10).await, [home]);
     }
 }
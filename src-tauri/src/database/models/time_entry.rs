@@ -0,0 +1,236 @@
+use chrono::{DateTime, Utc};
+use sea_query::{ColumnDef, Expr, Iden, Order, Query, SqliteQueryBuilder, Table};
+use sea_query_binder::SqlxBinder;
+use sqlx::{query, query_as_with, query_with, Executor, FromRow, Sqlite, SqlitePool};
+
+use crate::database::ensure_affected;
+
+pub struct TimeEntryEntity;
+
+/// a span of time spent on an item, the entry without `StoppedAt` is the running timer
+#[derive(Debug, Iden)]
+pub enum TimeEntry {
+    Table,
+    Id,
+    ItemId,
+    StartedAt,
+    StoppedAt,
+}
+
+impl TimeEntry {
+    fn get_columns() -> [Self; 4] {
+        [Self::Id, Self::ItemId, Self::StartedAt, Self::StoppedAt]
+    }
+}
+
+#[derive(Debug, FromRow, PartialEq)]
+pub struct TimeEntryModel {
+    pub id: i32,
+    pub item_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+impl TimeEntryEntity {
+    pub async fn create_table(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let sql = Table::create()
+            .table(TimeEntry::Table)
+            .if_not_exists()
+            .col(
+                ColumnDef::new(TimeEntry::Id)
+                    .integer()
+                    .auto_increment()
+                    .primary_key(),
+            )
+            .col(ColumnDef::new(TimeEntry::ItemId).integer().not_null())
+            .col(ColumnDef::new(TimeEntry::StartedAt).timestamp().not_null())
+            .col(ColumnDef::new(TimeEntry::StoppedAt).timestamp())
+            .build(SqliteQueryBuilder);
+
+        query(&sql).execute(pool).await?;
+        Ok(())
+    }
+
+    pub async fn save<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_id: i32,
+        started_at: DateTime<Utc>,
+        stopped_at: Option<DateTime<Utc>>,
+    ) -> Result<i32, sqlx::Error> {
+        let (sql, values) = Query::insert()
+            .into_table(TimeEntry::Table)
+            .columns([
+                TimeEntry::ItemId,
+                TimeEntry::StartedAt,
+                TimeEntry::StoppedAt,
+            ])
+            .values_panic([item_id.into(), started_at.into(), stopped_at.into()])
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(executor)
+            .await
+            .map(|result| result.last_insert_rowid() as i32)
+    }
+
+    pub async fn find_by_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+    ) -> Result<TimeEntryModel, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(TimeEntry::get_columns())
+            .from(TimeEntry::Table)
+            .and_where(Expr::col(TimeEntry::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_one(executor).await
+    }
+
+    /// the running timer, there is at most one
+    pub async fn find_running<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+    ) -> Result<Option<TimeEntryModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(TimeEntry::get_columns())
+            .from(TimeEntry::Table)
+            .and_where(Expr::col(TimeEntry::StoppedAt).is_null())
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_optional(executor).await
+    }
+
+    /// the entries of the item, the earliest started first
+    pub async fn fetch_all_by_item_id(
+        pool: &SqlitePool,
+        item_id: i32,
+    ) -> Result<Vec<TimeEntryModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(TimeEntry::get_columns())
+            .from(TimeEntry::Table)
+            .and_where(Expr::col(TimeEntry::ItemId).eq(item_id))
+            .order_by(TimeEntry::StartedAt, Order::Asc)
+            .order_by(TimeEntry::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    pub async fn fetch_all(pool: &SqlitePool) -> Result<Vec<TimeEntryModel>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .columns(TimeEntry::get_columns())
+            .from(TimeEntry::Table)
+            .order_by(TimeEntry::StartedAt, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    /// set the span of the entry, `stopped_at` none makes it running
+    pub async fn update<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+        started_at: DateTime<Utc>,
+        stopped_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(TimeEntry::Table)
+            .values([
+                (TimeEntry::StartedAt, started_at.into()),
+                (TimeEntry::StoppedAt, stopped_at.into()),
+            ])
+            .and_where(Expr::col(TimeEntry::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(executor)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    pub async fn remove(pool: &SqlitePool, id: i32) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(TimeEntry::Table)
+            .and_where(Expr::col(TimeEntry::Id).eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    pub async fn remove_all_by_item_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(TimeEntry::Table)
+            .and_where(Expr::col(TimeEntry::ItemId).is_in(item_ids))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_time_entry {
+    use chrono::{Duration, Utc};
+
+    use crate::database::models::test_sqlite::{init, item};
+
+    use super::TimeEntryEntity;
+
+    #[tokio::test]
+    async fn test_time_entry() {
+        let pool = init().await;
+        let foo = item("Foo").save(&pool).await;
+        let start = Utc::now() - Duration::hours(2);
+        let done = TimeEntryEntity::save(&pool, foo, start, Some(start + Duration::hours(1)))
+            .await
+            .unwrap();
+        assert!(TimeEntryEntity::find_running(&pool)
+            .await
+            .unwrap()
+            .is_none());
+
+        let running = TimeEntryEntity::save(&pool, foo, start + Duration::hours(1), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            TimeEntryEntity::find_running(&pool)
+                .await
+                .unwrap()
+                .map(|model| model.id),
+            Some(running)
+        );
+
+        let stop = Utc::now();
+        TimeEntryEntity::update(&pool, running, start + Duration::hours(1), Some(stop))
+            .await
+            .unwrap();
+        let entries = TimeEntryEntity::fetch_all_by_item_id(&pool, foo)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|model| (model.id, model.stopped_at))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                (done, Some(start + Duration::hours(1))),
+                (running, Some(stop))
+            ]
+        );
+
+        TimeEntryEntity::remove(&pool, done).await.unwrap();
+        assert!(matches!(
+            TimeEntryEntity::find_by_id(&pool, done).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+        TimeEntryEntity::remove_all_by_item_id(&pool, [foo])
+            .await
+            .unwrap();
+        assert!(TimeEntryEntity::fetch_all(&pool).await.unwrap().is_empty());
+    }
+}
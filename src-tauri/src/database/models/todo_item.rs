@@ -1,26 +1,91 @@
-use sea_query::{ColumnDef, Expr, Iden, Query, SqliteQueryBuilder, Table};
+use chrono::{DateTime, NaiveDate, Utc};
+use sea_query::{
+    ColumnDef, Cond, Condition, Expr, Func, Iden, LikeExpr, Order, Query, SelectStatement,
+    SimpleExpr, SqliteQueryBuilder, Table,
+};
 use sea_query_binder::SqlxBinder;
-use sqlx::{query, query_as_with, query_with, FromRow, SqlitePool};
+use sqlx::{
+    query, query_as_with, query_with, Executor, FromRow, Sqlite, SqliteConnection, SqlitePool,
+};
 use tap::{Conv, Pipe};
 
-use crate::database::priority::Priority;
+use crate::database::{
+    add_column_if_not_exists, ensure_affected, escape_like, priority::Priority, rank,
+};
+
+use super::tag_item_bind::TagItemBind;
 
 pub struct TodoItemEntity;
 
-#[derive(Debug, Iden)]
+#[derive(Debug, Clone, Copy, Iden)]
 pub enum TodoItem {
     Table,
     Id,
     Message,
     Priority,
     Done,
+    /// the key of the manual order, see [`rank`]
+    Rank,
+    /// the markdown notes, not in [`TodoItemModel`] as it can be long
+    Notes,
+    /// the estimate is in either minutes or points, the other one is null
+    EstimateMinutes,
+    EstimatePoints,
+    /// the day the item should be done by
+    DueOn,
+    /// null for the items from an older version
+    CreatedAt,
+    /// when the item is done, null while it is open. the items done in an older version
+    /// are done without it
+    CompletedAt,
+}
+
+/// the names of the sums selected by [`TodoItemEntity::select_estimate_sum`]
+#[derive(Debug, Iden)]
+enum EstimateSumAlias {
+    Items,
+    Estimated,
+    Minutes,
+    Points,
+}
+/// the names selected by the stats queries
+#[derive(Debug, Iden)]
+enum StatsAlias {
+    Day,
+    Count,
+    Items,
+    Done,
+}
+
+/// the sqlite functions on the timestamps
+#[derive(Debug, Iden)]
+enum TimeFn {
+    Date,
+    Julianday,
 }
+
 impl TodoItem {
-    fn get_columns() -> [Self; 4] {
-        [Self::Id, Self::Message, Self::Priority, Self::Done]
+    fn get_columns() -> [Self; 8] {
+        [
+            Self::Id,
+            Self::Message,
+            Self::Priority,
+            Self::Done,
+            Self::Rank,
+            Self::EstimateMinutes,
+            Self::EstimatePoints,
+            Self::DueOn,
+        ]
     }
-    fn columns_without_id() -> [Self; 3] {
-        [Self::Message, Self::Priority, Self::Done]
+    fn columns_without_id() -> [Self; 6] {
+        [
+            Self::Message,
+            Self::Priority,
+            Self::Done,
+            Self::Rank,
+            Self::CreatedAt,
+            Self::CompletedAt,
+        ]
     }
 
     fn get_table() -> Self {
@@ -38,6 +103,117 @@ pub struct TodoItemModel {
     pub message: String,
     pub priority: Priority,
     pub done: bool,
+    /// only none before [`TodoItemEntity::fill_rank`] for the items from an older version
+    pub rank: Option<String>,
+    pub estimate_minutes: Option<i64>,
+    pub estimate_points: Option<i64>,
+    pub due_on: Option<NaiveDate>,
+}
+
+/// the items whose timestamp falls on the day
+#[derive(Debug, FromRow, PartialEq)]
+pub struct DayCount {
+    pub day: NaiveDate,
+    pub count: i32,
+}
+
+/// the items created in a range in a priority, and how many of them are done
+#[derive(Debug, FromRow, PartialEq)]
+pub struct DoneCount {
+    pub priority: Priority,
+    pub items: i32,
+    pub done: i32,
+}
+
+/// a condition on the items, compiled to the `WHERE` of the select on the item table
+#[derive(Debug, Clone)]
+pub enum ItemCond {
+    /// every condition holds, true if empty
+    All(Vec<ItemCond>),
+    /// any condition holds, false if empty
+    Any(Vec<ItemCond>),
+    Not(Box<ItemCond>),
+    Done(bool),
+    Priority(Vec<Priority>),
+    /// bind with any of the tags
+    Tags(Vec<i32>),
+    /// the message or the notes contain the text
+    Text(String),
+    /// the column, which is a date or a timestamp, is not null and its day in UTC is in
+    /// the range, both ends included
+    Date {
+        col: TodoItem,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    },
+}
+
+impl ItemCond {
+    pub fn into_condition(self) -> Condition {
+        let col = |col: TodoItem| Expr::col((TodoItem::Table, col));
+        match self {
+            Self::All(conds) if conds.is_empty() => Cond::all().add(Expr::cust("TRUE")),
+            Self::All(conds) => conds
+                .into_iter()
+                .fold(Cond::all(), |all, cond| all.add(cond.into_condition())),
+            Self::Any(conds) if conds.is_empty() => Cond::all().add(Expr::cust("FALSE")),
+            Self::Any(conds) => conds
+                .into_iter()
+                .fold(Cond::any(), |any, cond| any.add(cond.into_condition())),
+            Self::Not(cond) => cond.into_condition().not(),
+            Self::Done(done) => Cond::all().add(col(TodoItem::Done).eq(done)),
+            Self::Priority(priorities) if priorities.is_empty() => {
+                Cond::all().add(Expr::cust("FALSE"))
+            }
+            Self::Priority(priorities) => {
+                Cond::all().add(col(TodoItem::Priority).is_in(priorities))
+            }
+            Self::Tags(tag_ids) if tag_ids.is_empty() => Cond::all().add(Expr::cust("FALSE")),
+            Self::Tags(tag_ids) => Cond::all().add(
+                col(TodoItem::Id).in_subquery(
+                    Query::select()
+                        .column(TagItemBind::ItemId)
+                        .from(TagItemBind::Table)
+                        .and_where(Expr::col(TagItemBind::TagId).is_in(tag_ids))
+                        .take(),
+                ),
+            ),
+            Self::Text(text) => {
+                let pattern = format!("%{}%", escape_like(&text));
+                Cond::any()
+                    .add(col(TodoItem::Message).like(LikeExpr::str(&pattern).escape('\\')))
+                    // the notes are NULL if not written, which must not make `Not` NULL too
+                    .add(
+                        Expr::expr(Func::if_null(col(TodoItem::Notes), Expr::val("")))
+                            .like(LikeExpr::str(&pattern).escape('\\')),
+                    )
+            }
+            Self::Date {
+                col: date_col,
+                from,
+                to,
+            } => {
+                let day = || Expr::expr(local_day(date_col, 0));
+                Cond::all()
+                    .add(col(date_col).is_not_null())
+                    .add_option(from.map(|from| day().gte(from)))
+                    .add_option(to.map(|to| day().lte(to)))
+            }
+        }
+    }
+}
+
+/// the estimates of the items in a priority and done state. the sums are none when no
+/// item in the group has an estimate in that unit
+#[derive(Debug, FromRow, PartialEq)]
+pub struct EstimateSum {
+    pub priority: Priority,
+    pub done: bool,
+    pub items: i32,
+    /// how many items have an estimate
+    pub estimated: i32,
+    pub minutes: Option<i64>,
+    pub points: Option<i64>,
 }
 
 impl TodoItemEntity {
@@ -66,9 +242,84 @@ impl TodoItemEntity {
             .build(SqliteQueryBuilder);
 
         query(&stet).execute(pool).await?;
+        add_column_if_not_exists(pool, TodoItem::Table, TodoItem::Rank, |col| col.text()).await?;
+        add_column_if_not_exists(pool, TodoItem::Table, TodoItem::Notes, |col| col.text()).await?;
+        add_column_if_not_exists(pool, TodoItem::Table, TodoItem::EstimateMinutes, |col| {
+            col.big_integer()
+        })
+        .await?;
+        add_column_if_not_exists(pool, TodoItem::Table, TodoItem::EstimatePoints, |col| {
+            col.big_integer()
+        })
+        .await?;
+        add_column_if_not_exists(pool, TodoItem::Table, TodoItem::DueOn, |col| col.date()).await?;
+        add_column_if_not_exists(pool, TodoItem::Table, TodoItem::CreatedAt, |col| {
+            col.timestamp()
+        })
+        .await?;
+        add_column_if_not_exists(pool, TodoItem::Table, TodoItem::CompletedAt, |col| {
+            col.timestamp()
+        })
+        .await?;
+        Self::fill_rank(pool).await?;
         Ok(())
     }
 
+    /// give the items without rank a place at the end of the list, in the order they created
+    async fn fill_rank(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let (sql, values) = Query::select()
+            .column(TodoItem::Id)
+            .from(TodoItem::get_table())
+            .and_where(TodoItem::Rank.into_col_expr().is_null())
+            .order_by(TodoItem::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+        let ids: Vec<(i32,)> = query_as_with(&sql, values).fetch_all(&mut tx).await?;
+
+        let mut last = Self::last_rank(&mut tx).await?;
+        for (id,) in ids {
+            let rank = next_rank(last.as_deref())?;
+            Self::update_rank(&mut tx, id, rank.clone()).await?;
+            last = Some(rank);
+        }
+        tx.commit().await
+    }
+
+    /// the rank of the last item in the list
+    async fn last_rank(conn: &mut SqliteConnection) -> Result<Option<String>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .expr(TodoItem::Rank.into_col_expr().max())
+            .from(TodoItem::get_table())
+            .build_sqlx(SqliteQueryBuilder);
+        let (rank,): (Option<String>,) = query_as_with(&sql, values).fetch_one(conn).await?;
+        Ok(rank)
+    }
+
+    /// count the items other than the given one ranked strictly between the two ranks,
+    /// an open end is not bounded
+    pub async fn count_between<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+        before: Option<&str>,
+        after: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .expr(TodoItem::Id.into_col_expr().count())
+            .from(TodoItem::get_table())
+            .and_where(TodoItem::Id.into_col_expr().ne(id))
+            .pipe(|query| match before {
+                Some(before) => query.and_where(TodoItem::Rank.into_col_expr().gt(before)),
+                None => query,
+            })
+            .pipe(|query| match after {
+                Some(after) => query.and_where(TodoItem::Rank.into_col_expr().lt(after)),
+                None => query,
+            })
+            .build_sqlx(SqliteQueryBuilder);
+        let (count,): (i64,) = query_as_with(&sql, values).fetch_one(executor).await?;
+        Ok(count)
+    }
+
     pub async fn fetch_all(
         pool: &SqlitePool,
         limit: impl Into<Option<u64>>,
@@ -76,6 +327,8 @@ impl TodoItemEntity {
         let (stet, values) = Query::select()
             .columns(TodoItem::get_columns())
             .from(TodoItem::get_table())
+            .order_by(TodoItem::Rank, Order::Asc)
+            .order_by(TodoItem::Id, Order::Asc)
             .pipe(|query| {
                 if let Some(limit) = limit.into() {
                     query.limit(limit)
@@ -88,39 +341,366 @@ impl TodoItemEntity {
         query_as_with(&stet, values).fetch_all(pool).await
     }
 
-    pub async fn find_all_by_id(
+    /// the items match the condition, in the manual order
+    pub async fn fetch_all_by_cond(
         pool: &SqlitePool,
+        cond: ItemCond,
+    ) -> Result<Vec<TodoItemModel>, sqlx::Error> {
+        let (stet, values) = Query::select()
+            .columns(TodoItem::get_columns())
+            .from(TodoItem::get_table())
+            .cond_where(cond.into_condition())
+            .order_by(TodoItem::Rank, Order::Asc)
+            .order_by(TodoItem::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&stet, values).fetch_all(pool).await
+    }
+
+    pub async fn find_all_by_id<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
         ids: impl IntoIterator<Item = i32>,
     ) -> Result<Vec<TodoItemModel>, sqlx::Error> {
         let (stet, values) = Query::select()
             .columns(TodoItem::get_columns())
             .from(TodoItem::get_table())
             .and_where(TodoItem::Id.into_col_expr().is_in(ids))
+            .order_by(TodoItem::Rank, Order::Asc)
+            .order_by(TodoItem::Id, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&stet, values).fetch_all(executor).await
+    }
+
+    /// the items whose message or notes contain the keyword, ignoring the ascii case
+    pub async fn search(
+        pool: &SqlitePool,
+        keyword: &str,
+    ) -> Result<Vec<TodoItemModel>, sqlx::Error> {
+        let pattern = format!("%{}%", escape_like(keyword));
+        let (stet, values) = Query::select()
+            .columns(TodoItem::get_columns())
+            .from(TodoItem::get_table())
+            .cond_where(
+                Cond::any()
+                    .add(
+                        TodoItem::Message
+                            .into_col_expr()
+                            .like(LikeExpr::str(&pattern).escape('\\')),
+                    )
+                    .add(
+                        TodoItem::Notes
+                            .into_col_expr()
+                            .like(LikeExpr::str(&pattern).escape('\\')),
+                    ),
+            )
+            .order_by(TodoItem::Rank, Order::Asc)
+            .order_by(TodoItem::Id, Order::Asc)
             .build_sqlx(SqliteQueryBuilder);
 
         query_as_with(&stet, values).fetch_all(pool).await
     }
 
+    /// the notes of the item, none if never written
+    pub async fn fetch_notes(pool: &SqlitePool, id: i32) -> Result<Option<String>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .column(TodoItem::Notes)
+            .from(TodoItem::get_table())
+            .and_where(TodoItem::Id.into_col_expr().eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        let (notes,): (Option<String>,) = query_as_with(&sql, values).fetch_one(pool).await?;
+        Ok(notes)
+    }
+
+    pub async fn update_notes(
+        pool: &SqlitePool,
+        id: i32,
+        notes: String,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(TodoItem::get_table())
+            .value(TodoItem::Notes, notes.into())
+            .and_where(TodoItem::Id.into_col_expr().eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
+    }
+
     pub async fn save(
         pool: &SqlitePool,
         message: String,
         priority: Priority,
         done: impl Into<Option<bool>>,
     ) -> Result<i32, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let id = Self::save_in(&mut tx, message, priority, done).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// [`TodoItemEntity::save`] on the connection, the caller keeps the rank read and the
+    /// insert in one transaction
+    pub async fn save_in(
+        conn: &mut SqliteConnection,
+        message: String,
+        priority: Priority,
+        done: impl Into<Option<bool>>,
+    ) -> Result<i32, sqlx::Error> {
+        // new item is placed at the end of the list
+        let rank = next_rank(Self::last_rank(&mut *conn).await?.as_deref())?;
+        let done = done.conv::<Option<bool>>().unwrap_or(false);
+        let now = Utc::now();
         let (sql, values) = Query::insert()
             .into_table(TodoItem::get_table())
             .columns(TodoItem::columns_without_id())
             .values_panic([
                 message.into(),
                 priority.into(),
-                done.conv::<Option<bool>>().unwrap_or(false).into(),
+                done.into(),
+                rank.into(),
+                now.into(),
+                done.then(|| now).into(),
+            ])
+            .build_sqlx(SqliteQueryBuilder);
+
+        let id = query_with(&sql, values)
+            .execute(conn)
+            .await?
+            .last_insert_rowid() as i32;
+        Ok(id)
+    }
+
+    /// set the estimate, at most one of `minutes` and `points` is expected
+    pub async fn update_estimate<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+        minutes: Option<i64>,
+        points: Option<i64>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(TodoItem::get_table())
+            .values([
+                (TodoItem::EstimateMinutes, minutes.into()),
+                (TodoItem::EstimatePoints, points.into()),
             ])
+            .and_where(TodoItem::Id.into_col_expr().eq(id))
             .build_sqlx(SqliteQueryBuilder);
 
         query_with(&sql, values)
-            .execute(pool)
+            .execute(executor)
             .await
-            .map(|result| result.last_insert_rowid() as i32)
+            .and_then(ensure_affected)
+    }
+
+    pub async fn update_due_on<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+        due_on: Option<NaiveDate>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(TodoItem::get_table())
+            .value(TodoItem::DueOn, due_on.into())
+            .and_where(TodoItem::Id.into_col_expr().eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(executor)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    /// sum the estimates of the items group by the priority and the done state,
+    /// the groups without any item are not included
+    pub async fn sum_estimate_by_priority(
+        pool: &SqlitePool,
+    ) -> Result<Vec<EstimateSum>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .column((TodoItem::Table, TodoItem::Priority))
+            .column((TodoItem::Table, TodoItem::Done))
+            .pipe(Self::select_estimate_sum)
+            .from(TodoItem::Table)
+            .group_by_col((TodoItem::Table, TodoItem::Priority))
+            .group_by_col((TodoItem::Table, TodoItem::Done))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    /// select the item count and the sums of the estimates of a group of items, the
+    /// query must have the item table joined
+    pub fn select_estimate_sum(query: &mut SelectStatement) -> &mut SelectStatement {
+        let col = |col: TodoItem| Expr::col((TodoItem::Table, col));
+        query
+            .expr_as(col(TodoItem::Id).count(), EstimateSumAlias::Items)
+            .expr_as(
+                col(TodoItem::EstimateMinutes)
+                    .count()
+                    .add(col(TodoItem::EstimatePoints).count()),
+                EstimateSumAlias::Estimated,
+            )
+            .expr_as(
+                col(TodoItem::EstimateMinutes).sum(),
+                EstimateSumAlias::Minutes,
+            )
+            .expr_as(
+                col(TodoItem::EstimatePoints).sum(),
+                EstimateSumAlias::Points,
+            )
+    }
+
+    /// count the items by the local day of the timestamp column, which is either
+    /// [`TodoItem::CreatedAt`] or [`TodoItem::CompletedAt`]. the days without any item
+    /// are not included
+    pub async fn count_by_day(
+        pool: &SqlitePool,
+        col: TodoItem,
+        utc_offset: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DayCount>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .expr_as(local_day(col, utc_offset), StatsAlias::Day)
+            .expr_as(Expr::col(TodoItem::Id).count(), StatsAlias::Count)
+            .from(TodoItem::Table)
+            .and_where(Expr::expr(local_day(col, utc_offset)).between(from, to))
+            .group_by_col(StatsAlias::Day)
+            .order_by(StatsAlias::Day, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    /// count the items open at the start of the local day, the items from an older
+    /// version are taken as created before any day
+    pub async fn count_open_before(
+        pool: &SqlitePool,
+        utc_offset: i32,
+        day: NaiveDate,
+    ) -> Result<i32, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .expr(Expr::col(TodoItem::Id).count())
+            .from(TodoItem::Table)
+            .cond_where(
+                Cond::all()
+                    .add(
+                        Cond::any()
+                            .add(TodoItem::CreatedAt.into_col_expr().is_null())
+                            .add(Expr::expr(local_day(TodoItem::CreatedAt, utc_offset)).lt(day)),
+                    )
+                    .add(
+                        Cond::any()
+                            .add(TodoItem::Done.into_col_expr().eq(false))
+                            .add(Expr::expr(local_day(TodoItem::CompletedAt, utc_offset)).gte(day)),
+                    ),
+            )
+            .build_sqlx(SqliteQueryBuilder);
+
+        let (count,): (i32,) = query_as_with(&sql, values).fetch_one(pool).await?;
+        Ok(count)
+    }
+
+    /// count the items created in the range and the done ones by the priority, the
+    /// priorities without any item are not included
+    pub async fn count_done_by_priority(
+        pool: &SqlitePool,
+        utc_offset: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<DoneCount>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .column((TodoItem::Table, TodoItem::Priority))
+            .pipe(Self::select_done_count)
+            .from(TodoItem::Table)
+            .and_where(Self::created_between(utc_offset, from, to))
+            .group_by_col((TodoItem::Table, TodoItem::Priority))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_as_with(&sql, values).fetch_all(pool).await
+    }
+
+    /// select the item count and the done count of a group of items, the query must have
+    /// the item table joined
+    pub fn select_done_count(query: &mut SelectStatement) -> &mut SelectStatement {
+        query
+            .expr_as(
+                Expr::col((TodoItem::Table, TodoItem::Id)).count(),
+                StatsAlias::Items,
+            )
+            .expr_as(
+                Expr::col((TodoItem::Table, TodoItem::Done)).sum(),
+                StatsAlias::Done,
+            )
+    }
+
+    /// the items created in the local days of the range
+    pub fn created_between(utc_offset: i32, from: NaiveDate, to: NaiveDate) -> SimpleExpr {
+        Expr::expr(local_day(TodoItem::CreatedAt, utc_offset)).between(from, to)
+    }
+
+    /// the average seconds from created to completed of the items completed in the range,
+    /// none if there is no such item
+    pub async fn average_seconds_to_complete(
+        pool: &SqlitePool,
+        utc_offset: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Option<f64>, sqlx::Error> {
+        let julianday = |col: TodoItem| Func::cust(TimeFn::Julianday).arg(Expr::col(col));
+        let (sql, values) = Query::select()
+            .expr(Func::avg(
+                Expr::expr(julianday(TodoItem::CompletedAt).sub(julianday(TodoItem::CreatedAt)))
+                    .mul(24 * 60 * 60),
+            ))
+            .from(TodoItem::Table)
+            .and_where(TodoItem::CreatedAt.into_col_expr().is_not_null())
+            .and_where(Expr::expr(local_day(TodoItem::CompletedAt, utc_offset)).between(from, to))
+            .build_sqlx(SqliteQueryBuilder);
+
+        let (average,): (Option<f64>,) = query_as_with(&sql, values).fetch_one(pool).await?;
+        Ok(average)
+    }
+
+    /// the local days any item is completed on, up to the day `until`, the earliest first
+    pub async fn fetch_completion_days(
+        pool: &SqlitePool,
+        utc_offset: i32,
+        until: NaiveDate,
+    ) -> Result<Vec<NaiveDate>, sqlx::Error> {
+        let (sql, values) = Query::select()
+            .distinct()
+            .expr_as(
+                local_day(TodoItem::CompletedAt, utc_offset),
+                StatsAlias::Day,
+            )
+            .from(TodoItem::Table)
+            .and_where(Expr::expr(local_day(TodoItem::CompletedAt, utc_offset)).lte(until))
+            .order_by(StatsAlias::Day, Order::Asc)
+            .build_sqlx(SqliteQueryBuilder);
+
+        let days: Vec<(NaiveDate,)> = query_as_with(&sql, values).fetch_all(pool).await?;
+        Ok(days.into_iter().map(|(day,)| day).collect())
+    }
+
+    /// move the item to the place of the rank, only this row is changed
+    pub async fn update_rank<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+        rank: String,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(TodoItem::get_table())
+            .value(TodoItem::Rank, rank.into())
+            .and_where(TodoItem::Id.into_col_expr().eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(executor)
+            .await
+            .and_then(ensure_affected)
     }
 
     pub async fn update_message(
@@ -134,12 +714,14 @@ impl TodoItemEntity {
             .and_where(TodoItem::Id.into_col_expr().eq(id))
             .build_sqlx(SqliteQueryBuilder);
 
-        query_with(&sql, values).execute(pool).await?;
-        Ok(())
+        query_with(&sql, values)
+            .execute(pool)
+            .await
+            .and_then(ensure_affected)
     }
 
-    pub async fn update_priority(
-        pool: &SqlitePool,
+    pub async fn update_priority<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
         id: i32,
         priority: Priority,
     ) -> Result<(), sqlx::Error> {
@@ -149,44 +731,152 @@ impl TodoItemEntity {
             .and_where(TodoItem::Id.into_col_expr().eq(id))
             .build_sqlx(SqliteQueryBuilder);
 
-        query_with(&sql, values).execute(pool).await?;
-        Ok(())
+        query_with(&sql, values)
+            .execute(executor)
+            .await
+            .and_then(ensure_affected)
     }
 
-    pub async fn revert_done(pool: &SqlitePool, id: i32) -> Result<(), sqlx::Error> {
+    pub async fn revert_done<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+    ) -> Result<(), sqlx::Error> {
         let (sql, values) = Query::update()
             .table(TodoItem::get_table())
             .value_expr(TodoItem::Done, TodoItem::Done.into_col_expr().not())
+            .value_expr(
+                TodoItem::CompletedAt,
+                Expr::cust_with_values("CASE WHEN done THEN NULL ELSE ? END", [Utc::now()]),
+            )
+            .and_where(TodoItem::Id.into_col_expr().eq(id))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values)
+            .execute(executor)
+            .await
+            .and_then(ensure_affected)
+    }
+
+    /// set the done state, unlike [`TodoItemEntity::revert_done`] apply it twice
+    /// has the same result. return the number of rows affected
+    pub async fn set_done<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+        done: bool,
+    ) -> Result<u64, sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(TodoItem::get_table())
+            .value(TodoItem::Done, done.into())
+            .value_expr(TodoItem::CompletedAt, completed_at(done))
             .and_where(TodoItem::Id.into_col_expr().eq(id))
             .build_sqlx(SqliteQueryBuilder);
 
-        query_with(&sql, values).execute(pool).await?;
+        query_with(&sql, values)
+            .execute(executor)
+            .await
+            .map(|result| result.rows_affected())
+    }
+
+    /// set the done state of all the items, for the bulk operate in a transaction
+    pub async fn update_done_all<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        ids: impl IntoIterator<Item = i32>,
+        done: bool,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(TodoItem::get_table())
+            .value(TodoItem::Done, done.into())
+            .value_expr(TodoItem::CompletedAt, completed_at(done))
+            .and_where(TodoItem::Id.into_col_expr().is_in(ids))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
         Ok(())
     }
 
-    pub async fn remove(pool: &SqlitePool, id: i32) -> Result<(), sqlx::Error> {
+    pub async fn update_priority_all<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        ids: impl IntoIterator<Item = i32>,
+        priority: Priority,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::update()
+            .table(TodoItem::get_table())
+            .value(TodoItem::Priority, priority.into())
+            .and_where(TodoItem::Id.into_col_expr().is_in(ids))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+
+    pub async fn remove_all<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        ids: impl IntoIterator<Item = i32>,
+    ) -> Result<(), sqlx::Error> {
+        let (sql, values) = Query::delete()
+            .from_table(TodoItem::get_table())
+            .and_where(TodoItem::Id.into_col_expr().is_in(ids))
+            .build_sqlx(SqliteQueryBuilder);
+
+        query_with(&sql, values).execute(executor).await?;
+        Ok(())
+    }
+
+    pub async fn remove<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        id: i32,
+    ) -> Result<(), sqlx::Error> {
         let (sql, values) = Query::delete()
             .from_table(TodoItem::get_table())
             .and_where(TodoItem::Id.into_col_expr().eq(id))
             .build_sqlx(SqliteQueryBuilder);
 
-        query_with(&sql, values).execute(pool).await?;
+        query_with(&sql, values)
+            .execute(executor)
+            .await
+            .and_then(ensure_affected)
+    }
+}
 
-        Ok(())
+/// the day of the timestamp column in the time zone `utc_offset` minutes east of UTC,
+/// null if the timestamp is null
+fn local_day(col: TodoItem, utc_offset: i32) -> SimpleExpr {
+    Func::cust(TimeFn::Date).args([
+        Expr::col((TodoItem::Table, col)),
+        Expr::val(format!("{utc_offset:+} minutes")),
+    ])
+}
+
+/// the completion time after the done state is set, an item done already keeps its own
+fn completed_at(done: bool) -> SimpleExpr {
+    if done {
+        Expr::cust_with_values("CASE WHEN done THEN completed_at ELSE ? END", [Utc::now()])
+    } else {
+        Expr::val(Option::<DateTime<Utc>>::None).into()
     }
 }
 
+/// the rank after the last one, the ranks are always made by [`rank`], so a bad one is
+/// reported as the data in the database is broken
+fn next_rank(last: Option<&str>) -> Result<String, sqlx::Error> {
+    rank::between(last, None)
+        .ok_or_else(|| sqlx::Error::Decode(format!("malformed rank `{last:?}`").into()))
+}
+
 #[cfg(test)]
 mod test_todo_item {
 
-    use sqlx::SqlitePool;
+    use sqlx::{query, SqlitePool};
+
+    use chrono::NaiveDate;
 
     use crate::database::{
-        models::test_sqlite::{init, SQLITE},
+        models::test_sqlite::{init, item, tag},
         priority::Priority,
+        rank,
     };
 
-    use super::{TodoItemEntity, TodoItemModel};
+    use super::{EstimateSum, ItemCond, TodoItem, TodoItemEntity, TodoItemModel};
 
     async fn get_model(pool: &SqlitePool, id: i32) -> TodoItemModel {
         let model = TodoItemEntity::find_all_by_id(pool, [id])
@@ -199,13 +889,12 @@ mod test_todo_item {
 
     #[tokio::test]
     async fn test_create() {
-        init().await;
-        let pool = SQLITE.get().unwrap();
-        let idx = TodoItemEntity::save(pool, "Foo".into(), Priority::VeryHigh, None)
+        let pool = init().await;
+        let idx = TodoItemEntity::save(&pool, "Foo".into(), Priority::VeryHigh, None)
             .await
             .unwrap();
 
-        let model = get_model(pool, idx).await;
+        let model = get_model(&pool, idx).await;
 
         assert_eq!(
             model,
@@ -213,28 +902,193 @@ mod test_todo_item {
                 id: idx,
                 message: "Foo".into(),
                 priority: Priority::VeryHigh,
-                done: false
+                done: false,
+                rank: Some("a0".into()),
+                estimate_minutes: None,
+                estimate_points: None,
+                due_on: None,
             }
         );
     }
 
     #[tokio::test]
     async fn test_rev() {
-        init().await;
-        let pool = SQLITE.get().unwrap();
+        let pool = init().await;
+        let idx = item("Foo").priority(Priority::VeryHigh).save(&pool).await;
 
-        TodoItemEntity::revert_done(pool, 1).await.unwrap();
+        TodoItemEntity::revert_done(&pool, idx).await.unwrap();
 
-        let model = get_model(pool, 1).await;
+        let model = get_model(&pool, idx).await;
 
         assert_eq!(
             model,
             TodoItemModel {
-                id: 1,
+                id: idx,
                 message: "Foo".into(),
                 priority: Priority::VeryHigh,
-                done: true
+                done: true,
+                rank: Some("a0".into()),
+                estimate_minutes: None,
+                estimate_points: None,
+                due_on: None,
             }
         )
     }
+
+    #[tokio::test]
+    async fn test_estimate() {
+        let pool = init().await;
+        let foo = item("Foo").save(&pool).await;
+        let bar = item("Bar").save(&pool).await;
+        item("Baz").done().save(&pool).await;
+        TodoItemEntity::update_estimate(&pool, foo, Some(30), None)
+            .await
+            .unwrap();
+        TodoItemEntity::update_estimate(&pool, bar, None, Some(3))
+            .await
+            .unwrap();
+        assert!(matches!(
+            TodoItemEntity::update_estimate(&pool, bar + 10, None, None).await,
+            Err(sqlx::Error::RowNotFound)
+        ));
+
+        let mut sums = TodoItemEntity::sum_estimate_by_priority(&pool)
+            .await
+            .unwrap();
+        sums.sort_by_key(|sum| sum.done);
+        assert_eq!(
+            sums,
+            [
+                EstimateSum {
+                    priority: Priority::Medium,
+                    done: false,
+                    items: 2,
+                    estimated: 2,
+                    minutes: Some(30),
+                    points: Some(3),
+                },
+                EstimateSum {
+                    priority: Priority::Medium,
+                    done: true,
+                    items: 1,
+                    estimated: 0,
+                    minutes: None,
+                    points: None,
+                }
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cond() {
+        let pool = init().await;
+        let foo = item("Foo").tag("a").save(&pool).await;
+        let bar = item("Bar 100%")
+            .priority(Priority::High)
+            .tag("b")
+            .done()
+            .save(&pool)
+            .await;
+        let baz = item("Baz").priority(Priority::Low).save(&pool).await;
+        let due = NaiveDate::from_ymd(2022, 10, 20);
+        TodoItemEntity::update_due_on(&pool, baz, Some(due))
+            .await
+            .unwrap();
+        let a = tag(&pool, "a").await;
+
+        let fetch = |cond: ItemCond| {
+            let pool = pool.clone();
+            async move {
+                TodoItemEntity::fetch_all_by_cond(&pool, cond)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|model| model.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert_eq!(fetch(ItemCond::All(vec![])).await, [foo, bar, baz]);
+        assert!(fetch(ItemCond::Any(vec![])).await.is_empty());
+        assert_eq!(
+            fetch(ItemCond::Not(Box::new(ItemCond::Any(vec![])))).await,
+            [foo, bar, baz]
+        );
+        assert_eq!(
+            fetch(ItemCond::All(vec![
+                ItemCond::Done(false),
+                ItemCond::Not(Box::new(ItemCond::Tags(vec![a]))),
+            ]))
+            .await,
+            [baz]
+        );
+        assert_eq!(
+            fetch(ItemCond::Any(vec![
+                ItemCond::Tags(vec![a]),
+                ItemCond::Priority(vec![Priority::Low]),
+            ]))
+            .await,
+            [foo, baz]
+        );
+        // the wildcards are matched as they are
+        assert_eq!(fetch(ItemCond::Text("0%".into())).await, [bar]);
+        assert!(fetch(ItemCond::Text("B_r".into())).await.is_empty());
+
+        let date = |from, to| ItemCond::Date {
+            col: TodoItem::DueOn,
+            from,
+            to,
+        };
+        assert_eq!(fetch(date(None, None)).await, [baz]);
+        assert_eq!(fetch(date(Some(due), Some(due))).await, [baz]);
+        assert!(fetch(date(None, due.pred_opt())).await.is_empty());
+        assert_eq!(
+            fetch(ItemCond::Date {
+                col: TodoItem::CompletedAt,
+                from: None,
+                to: None,
+            })
+            .await,
+            [bar]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rank() {
+        let pool = init().await;
+        let a = item("A").save(&pool).await;
+        let b = item("B").save(&pool).await;
+        let c = item("C").save(&pool).await;
+        let ids = |models: Vec<TodoItemModel>| models.into_iter().map(|m| m.id).collect::<Vec<_>>();
+
+        // new items are appended
+        assert_eq!(
+            ids(TodoItemEntity::fetch_all(&pool, None).await.unwrap()),
+            [a, b, c]
+        );
+
+        // move `c` to the front
+        let first = get_model(&pool, a).await.rank;
+        let rank = rank::between(None, first.as_deref()).unwrap();
+        TodoItemEntity::update_rank(&pool, c, rank).await.unwrap();
+        assert_eq!(
+            ids(TodoItemEntity::fetch_all(&pool, None).await.unwrap()),
+            [c, a, b]
+        );
+        assert_eq!(
+            ids(TodoItemEntity::find_all_by_id(&pool, [b, c]).await.unwrap()),
+            [c, b]
+        );
+
+        // the items from an older version are placed after the others in the order of id
+        query("UPDATE todo_item SET rank = NULL WHERE id <> ?")
+            .bind(c)
+            .execute(&pool)
+            .await
+            .unwrap();
+        TodoItemEntity::fill_rank(&pool).await.unwrap();
+        assert_eq!(
+            ids(TodoItemEntity::fetch_all(&pool, None).await.unwrap()),
+            [c, a, b]
+        );
+    }
 }
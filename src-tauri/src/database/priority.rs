@@ -1,9 +1,9 @@
 use sqlx::{Database, Decode, Encode, Sqlite, Type};
 
-use crate::todo_storage::PriorityLevel;
+use crate::service::PriorityLevel;
 
 #[repr(u8)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Priority {
     VeryHigh = 0,
     High = 1,
@@ -13,7 +13,7 @@ pub enum Priority {
 }
 
 #[derive(Debug, thiserror::Error)]
-#[error("Expect u8 from 9 to 4, but get {0}")]
+#[error("Expect u8 from 0 to 4, but get {0}")]
 pub struct BadPriorityError(u8);
 
 impl TryFrom<u8> for Priority {
@@ -45,7 +45,7 @@ impl From<Priority> for sea_query::Value {
 
 impl From<&Priority> for u8 {
     fn from(val: &Priority) -> Self {
-        (*val) as u8
+        *val as u8
     }
 }
 
@@ -97,3 +97,62 @@ impl<'q> Encode<'q, Sqlite> for Priority {
         v.encode_by_ref(buf)
     }
 }
+
+#[cfg(test)]
+mod test_priority {
+    use proptest::prelude::*;
+    use sqlx::{query_as, Sqlite};
+
+    use crate::{database::models::test_sqlite::init, service::PriorityLevel};
+
+    use super::Priority;
+
+    fn priority() -> impl Strategy<Value = Priority> {
+        prop_oneof![
+            Just(Priority::VeryHigh),
+            Just(Priority::High),
+            Just(Priority::Medium),
+            Just(Priority::Low),
+            Just(Priority::VeryLow),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_u8_round_trip(value: u8) {
+            match Priority::try_from(value) {
+                Ok(priority) => prop_assert_eq!(u8::from(priority), value),
+                Err(_) => prop_assert!(value > 4),
+            }
+        }
+
+        #[test]
+        fn test_level_round_trip(priority in priority()) {
+            let level = PriorityLevel::from(priority);
+            prop_assert_eq!(Priority::from(level), priority);
+            // the order of levels follow the order of the numbers
+            prop_assert_eq!(level as u8, u8::from(priority));
+        }
+
+        #[test]
+        fn test_sqlx_round_trip(priority in priority(), value: u8) {
+            tokio::runtime::Runtime::new().unwrap().block_on(async {
+                let pool = init().await;
+
+                let (decoded,): (Priority,) = query_as::<Sqlite, _>("SELECT ?")
+                    .bind(priority)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap();
+                assert_eq!(decoded, priority);
+
+                // number out of range can not decode to priority
+                let decoded = query_as::<Sqlite, (Priority,)>("SELECT ?")
+                    .bind(value)
+                    .fetch_one(&pool)
+                    .await;
+                assert_eq!(decoded.is_ok(), value <= 4);
+            });
+        }
+    }
+}
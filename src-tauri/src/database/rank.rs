@@ -0,0 +1,228 @@
+//! lexicographic rank of the manual order, a key can always be made between any two keys,
+//! so moving an item only rewrites the key of that item
+//!
+//! a key is an integer part followed by a fraction part, both with base 62 digits. the head
+//! of the integer part tells its length, `a`-`z` for the positive integers with 1 to 26
+//! digits and `Z`-`A` for the negative ones, so appending to the end or the start of the
+//! list grows the key in logarithmic. keys compare as bytes, same as the `TEXT` of sqlite
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: usize = DIGITS.len();
+/// the smallest integer part, there is no key before it without a fraction part
+const SMALLEST_INTEGER: &str = "A00000000000000000000000000";
+
+/// a key sort after `before` and before `after`, `None` stand for the start or the end of
+/// the list. return `None` if `before` not sort before `after` or a key is malformed
+pub fn between(before: Option<&str>, after: Option<&str>) -> Option<String> {
+    match (before, after) {
+        (None, None) => Some("a0".into()),
+        (None, Some(after)) => {
+            let (integer, fraction) = split(after)?;
+            if integer == SMALLEST_INTEGER {
+                let fraction = midpoint(&[], Some(&to_digits(fraction)?))?;
+                Some(format!("{integer}{}", from_digits(&fraction)))
+            } else if !fraction.is_empty() {
+                Some(integer.into())
+            } else {
+                decrement(integer)
+            }
+        }
+        (Some(before), None) => {
+            let (integer, fraction) = split(before)?;
+            increment(integer).or_else(|| {
+                let fraction = midpoint(&to_digits(fraction)?, None)?;
+                Some(format!("{integer}{}", from_digits(&fraction)))
+            })
+        }
+        (Some(before), Some(after)) => {
+            if before >= after {
+                return None;
+            }
+            let (before_integer, before_fraction) = split(before)?;
+            let (after_integer, after_fraction) = split(after)?;
+            if before_integer == after_integer {
+                let fraction = midpoint(
+                    &to_digits(before_fraction)?,
+                    Some(&to_digits(after_fraction)?),
+                )?;
+                return Some(format!("{before_integer}{}", from_digits(&fraction)));
+            }
+            match increment(before_integer) {
+                Some(integer) if integer.as_str() < after => Some(integer),
+                _ => {
+                    let fraction = midpoint(&to_digits(before_fraction)?, None)?;
+                    Some(format!("{before_integer}{}", from_digits(&fraction)))
+                }
+            }
+        }
+    }
+}
+
+/// the digits between the fraction `low` and `high`, `None` for `high` is 1.
+/// the fractions never end with `0`, so there is always room before them
+fn midpoint(low: &[usize], high: Option<&[usize]>) -> Option<Vec<usize>> {
+    if let Some(high) = high {
+        // keep the common prefix, `low` is padded with `0`
+        let common = high
+            .iter()
+            .enumerate()
+            .take_while(|(idx, digit)| low.get(*idx).copied().unwrap_or(0) == **digit)
+            .count();
+        if common == high.len() {
+            return None;
+        }
+        if common > 0 {
+            let rest = midpoint(low.get(common..).unwrap_or(&[]), Some(&high[common..]))?;
+            return Some(high[..common].iter().copied().chain(rest).collect());
+        }
+    }
+
+    let low_digit = low.first().copied().unwrap_or(0);
+    let high_digit = high.map_or(BASE, |high| high[0]);
+    if high_digit <= low_digit {
+        None
+    } else if high_digit - low_digit > 1 {
+        Some(vec![(low_digit + high_digit) / 2])
+    } else {
+        match high {
+            // the first digit of `high` alone is already smaller than `high`
+            Some(high) if high.len() > 1 => Some(vec![high[0]]),
+            _ => {
+                let rest = midpoint(low.get(1..).unwrap_or(&[]), None)?;
+                Some([low_digit].into_iter().chain(rest).collect())
+            }
+        }
+    }
+}
+
+fn integer_len(head: u8) -> Option<usize> {
+    match head {
+        b'a'..=b'z' => Some((head - b'a') as usize + 2),
+        b'A'..=b'Z' => Some((b'Z' - head) as usize + 2),
+        _ => None,
+    }
+}
+
+/// split the key into the integer part and the fraction part
+fn split(key: &str) -> Option<(&str, &str)> {
+    let len = integer_len(*key.as_bytes().first()?)?;
+    if key.len() < len || !key.is_ascii() {
+        return None;
+    }
+    Some(key.split_at(len))
+}
+
+fn increment(integer: &str) -> Option<String> {
+    let (head, digits) = integer.split_at(1);
+    let mut digits = to_digits(digits)?;
+    for digit in digits.iter_mut().rev() {
+        if *digit + 1 < BASE {
+            *digit += 1;
+            return Some(format!("{head}{}", from_digits(&digits)));
+        }
+        *digit = 0;
+    }
+    // carry over the head
+    match head.as_bytes()[0] {
+        b'Z' => Some("a0".into()),
+        b'z' => None,
+        head => {
+            let head = head + 1;
+            if head > b'a' {
+                digits.push(0);
+            } else {
+                digits.pop();
+            }
+            Some(format!("{}{}", head as char, from_digits(&digits)))
+        }
+    }
+}
+
+fn decrement(integer: &str) -> Option<String> {
+    let (head, digits) = integer.split_at(1);
+    let mut digits = to_digits(digits)?;
+    for digit in digits.iter_mut().rev() {
+        if *digit > 0 {
+            *digit -= 1;
+            return Some(format!("{head}{}", from_digits(&digits)));
+        }
+        *digit = BASE - 1;
+    }
+    // borrow from the head
+    match head.as_bytes()[0] {
+        b'a' => Some(format!("Z{}", DIGITS[BASE - 1] as char)),
+        b'A' => None,
+        head => {
+            let head = head - 1;
+            if head < b'Z' {
+                digits.push(BASE - 1);
+            } else {
+                digits.pop();
+            }
+            Some(format!("{}{}", head as char, from_digits(&digits)))
+        }
+    }
+}
+
+fn to_digits(text: &str) -> Option<Vec<usize>> {
+    text.bytes()
+        .map(|byte| DIGITS.iter().position(|digit| *digit == byte))
+        .collect()
+}
+
+fn from_digits(digits: &[usize]) -> String {
+    digits.iter().map(|digit| DIGITS[*digit] as char).collect()
+}
+
+#[cfg(test)]
+mod test_rank {
+    use proptest::prelude::*;
+
+    use super::between;
+
+    #[test]
+    fn test_between() {
+        assert_eq!(between(None, None).unwrap(), "a0");
+        assert_eq!(between(Some("a0"), None).unwrap(), "a1");
+        assert_eq!(between(Some("az"), None).unwrap(), "b00");
+        assert_eq!(between(None, Some("a0")).unwrap(), "Zz");
+        assert_eq!(between(Some("a0"), Some("a1")).unwrap(), "a0V");
+        assert_eq!(between(Some("a0"), Some("a0V")).unwrap(), "a0F");
+        assert_eq!(between(Some("a0V"), Some("a1")).unwrap(), "a0k");
+
+        assert!(between(Some("a1"), Some("a0")).is_none());
+        assert!(between(Some("a1"), Some("a1")).is_none());
+        assert!(between(Some("?"), None).is_none());
+    }
+
+    #[test]
+    fn test_append_grows_slowly() {
+        let mut key = between(None, None).unwrap();
+        for _ in 0..10_000 {
+            let next = between(Some(&key), None).unwrap();
+            assert!(key < next);
+            key = next;
+        }
+        assert!(key.len() <= 4, "{key}");
+    }
+
+    proptest! {
+        #[test]
+        fn test_insert_anywhere(positions in prop::collection::vec(any::<usize>(), 1..64)) {
+            let mut keys: Vec<String> = vec![];
+            for position in positions {
+                let idx = position % (keys.len() + 1);
+                let before = idx.checked_sub(1).map(|idx| keys[idx].as_str());
+                let after = keys.get(idx).map(String::as_str);
+                let key = between(before, after).unwrap();
+                if let Some(before) = before {
+                    prop_assert!(before < key.as_str());
+                }
+                if let Some(after) = after {
+                    prop_assert!(key.as_str() < after);
+                }
+                keys.insert(idx, key);
+            }
+        }
+    }
+}
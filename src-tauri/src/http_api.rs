@@ -0,0 +1,418 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+use axum::{
+    extract::{Extension, Path, Query},
+    http::{header::AUTHORIZATION, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::HttpApiConfig;
+use crate::service::{
+    DoneState, EditMode, FieldError, PriorityLevel, ServiceError, Tag, TagMeta, TodoItem,
+    TodoService,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpApiError {
+    #[error("http api enabled without a token or with an empty one")]
+    MissingToken,
+    #[error("http server failure: {0}")]
+    Server(#[from] hyper::Error),
+}
+
+#[derive(Debug, Clone)]
+struct ApiToken(String);
+
+/// the error of handlers, response with status 400 for the invalid input, 404 for the
+/// missing rows, 409 for the changes conflict with the dependencies or 500 for the
+/// others, with the message in json body
+struct ApiError(ServiceError);
+
+impl From<ServiceError> for ApiError {
+    fn from(err: ServiceError) -> Self {
+        Self(err)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        #[derive(Serialize)]
+        struct Body {
+            error: String,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            fields: Vec<FieldError>,
+        }
+        let error = self.0.to_string();
+        let (status, fields) = match self.0 {
+            ServiceError::Validation(err) => (StatusCode::BAD_REQUEST, err.0),
+            err if err.is_not_found() => (StatusCode::NOT_FOUND, vec![]),
+            ServiceError::ItemBlocked { .. } | ServiceError::DependencyCycle { .. } => {
+                (StatusCode::CONFLICT, vec![])
+            }
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, vec![]),
+        };
+        (status, Json(Body { error, fields })).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// serve the todo store on localhost, sharing the service with the tauri app
+pub async fn serve(service: TodoService, config: HttpApiConfig) -> Result<(), HttpApiError> {
+    let token = config
+        .token
+        .filter(|token| !token.is_empty())
+        .ok_or(HttpApiError::MissingToken)?;
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, config.port));
+
+    axum::Server::bind(&addr)
+        .serve(router(service, token).into_make_service())
+        .await?;
+    Ok(())
+}
+
+fn router(service: TodoService, token: String) -> Router {
+    Router::new()
+        // todo
+        .route("/todos", get(list_todo_items).post(create_todo_item))
+        .route("/todos/:item_id", delete(delete_todo_item))
+        .route("/todos/:item_id/message", put(edit_message))
+        .route("/todos/:item_id/priority", put(edit_priority))
+        .route("/todos/:item_id/toggle", post(toggle_todo_item))
+        .route("/todos/:item_id/done", put(set_done))
+        .route("/todos/:item_id/position", put(move_item))
+        .route("/todos/:item_id/tags", post(edit_tag).delete(clean_tag))
+        // tag
+        .route("/tags", get(list_tags).post(create_tag).delete(delete_tag))
+        .route("/tags/:tag_id/name", put(rename_tag))
+        .route("/tags/:tag_id/todos", get(list_tag_todo_items))
+        .route_layer(middleware::from_fn(authorize))
+        .layer(Extension(ApiToken(token)))
+        .layer(Extension(service))
+}
+
+async fn authorize<B>(req: Request<B>, next: Next<B>) -> Result<Response, StatusCode> {
+    let ApiToken(token) = req
+        .extensions()
+        .get::<ApiToken>()
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let authorized = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map_or(false, |value| constant_time_eq(value, token));
+
+    if authorized {
+        Ok(next.run(req).await)
+    } else {
+        Err(StatusCode::UNAUTHORIZED)
+    }
+}
+
+/// compare all the bytes whatever the first difference is, so the time taken does not
+/// tell how much of the token is guessed right
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageBody {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PriorityBody {
+    priority: PriorityLevel,
+}
+
+#[derive(Debug, Deserialize)]
+struct DoneBody {
+    done: bool,
+}
+
+/// the neighbours of the new place, missing one means the start or the end of the list
+#[derive(Debug, Deserialize)]
+struct PositionBody {
+    before_id: Option<i32>,
+    after_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagTodoQuery {
+    #[serde(default)]
+    descendants: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagNameBody {
+    tag_name: Tag,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagBody {
+    mode: EditMode,
+    tag_name: Tag,
+}
+
+async fn list_todo_items(
+    Extension(service): Extension<TodoService>,
+) -> ApiResult<Vec<(i32, TodoItem)>> {
+    Ok(Json(service.fetch_all_todo_item().await?))
+}
+
+async fn create_todo_item(
+    Extension(service): Extension<TodoService>,
+    Json(item): Json<TodoItem>,
+) -> ApiResult<i32> {
+    Ok(Json(service.save_todo_item(item).await?))
+}
+
+async fn edit_message(
+    Extension(service): Extension<TodoService>,
+    Path(item_id): Path<i32>,
+    Json(MessageBody { message }): Json<MessageBody>,
+) -> ApiResult<()> {
+    service.edit_message(item_id, message).await?;
+    Ok(Json(()))
+}
+
+async fn edit_priority(
+    Extension(service): Extension<TodoService>,
+    Path(item_id): Path<i32>,
+    Json(PriorityBody { priority }): Json<PriorityBody>,
+) -> ApiResult<()> {
+    service.edit_priority(item_id, priority).await?;
+    Ok(Json(()))
+}
+
+async fn toggle_todo_item(
+    Extension(service): Extension<TodoService>,
+    Path(item_id): Path<i32>,
+) -> ApiResult<()> {
+    service.revert_done(item_id).await?;
+    Ok(Json(()))
+}
+
+async fn set_done(
+    Extension(service): Extension<TodoService>,
+    Path(item_id): Path<i32>,
+    Json(DoneBody { done }): Json<DoneBody>,
+) -> ApiResult<DoneState> {
+    Ok(Json(service.set_done(item_id, done).await?))
+}
+
+async fn move_item(
+    Extension(service): Extension<TodoService>,
+    Path(item_id): Path<i32>,
+    Json(PositionBody {
+        before_id,
+        after_id,
+    }): Json<PositionBody>,
+) -> ApiResult<()> {
+    service.move_item(item_id, before_id, after_id).await?;
+    Ok(Json(()))
+}
+
+async fn edit_tag(
+    Extension(service): Extension<TodoService>,
+    Path(item_id): Path<i32>,
+    Json(TagBody { mode, tag_name }): Json<TagBody>,
+) -> ApiResult<i32> {
+    Ok(Json(service.edit_tag(item_id, mode, tag_name).await?))
+}
+
+async fn clean_tag(
+    Extension(service): Extension<TodoService>,
+    Path(item_id): Path<i32>,
+) -> ApiResult<()> {
+    service.clean_tag(item_id).await?;
+    Ok(Json(()))
+}
+
+async fn delete_todo_item(
+    Extension(service): Extension<TodoService>,
+    Path(item_id): Path<i32>,
+) -> ApiResult<()> {
+    service.delete_todo_item(item_id).await?;
+    Ok(Json(()))
+}
+
+async fn list_tags(
+    Extension(service): Extension<TodoService>,
+) -> ApiResult<Vec<(i32, Tag, TagMeta)>> {
+    Ok(Json(service.fetch_all_tags().await?))
+}
+
+async fn create_tag(
+    Extension(service): Extension<TodoService>,
+    Json(TagNameBody { tag_name }): Json<TagNameBody>,
+) -> ApiResult<i32> {
+    Ok(Json(service.create_tag(tag_name).await?))
+}
+
+async fn rename_tag(
+    Extension(service): Extension<TodoService>,
+    Path(tag_id): Path<i32>,
+    Json(TagNameBody { tag_name }): Json<TagNameBody>,
+) -> ApiResult<()> {
+    service.rename_tag(tag_id, tag_name).await?;
+    Ok(Json(()))
+}
+
+/// the tag is given by its name in the body, the names may contain `/`
+async fn delete_tag(
+    Extension(service): Extension<TodoService>,
+    Json(TagNameBody { tag_name }): Json<TagNameBody>,
+) -> ApiResult<()> {
+    service.delete_tag(tag_name).await?;
+    Ok(Json(()))
+}
+
+async fn list_tag_todo_items(
+    Extension(service): Extension<TodoService>,
+    Path(tag_id): Path<i32>,
+    Query(TagTodoQuery { descendants }): Query<TagTodoQuery>,
+) -> ApiResult<Vec<(i32, TodoItem)>> {
+    Ok(Json(
+        service.fetch_all_tag_todo_item(tag_id, descendants).await?,
+    ))
+}
+
+#[cfg(test)]
+mod test_http_api {
+    use axum::{
+        body::Body,
+        http::{header::CONTENT_TYPE, Method, Request, StatusCode},
+        Router,
+    };
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use crate::{config::HttpApiConfig, service::test_service::service};
+
+    use super::{constant_time_eq, router, serve, HttpApiError};
+
+    async fn app() -> Router {
+        router(service().await, "secret".into())
+    }
+
+    fn request(method: Method, uri: &str, token: Option<&str>, body: Value) -> Request<Body> {
+        let mut builder = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(token) = token {
+            builder = builder.header("Authorization", format!("Bearer {token}"));
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    async fn send(app: Router, request: Request<Body>) -> (StatusCode, Value) {
+        let response = app.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secreT"));
+        assert!(!constant_time_eq("secret", "secret2"));
+        assert!(!constant_time_eq("", "secret"));
+    }
+
+    #[tokio::test]
+    async fn test_empty_token() {
+        let config = HttpApiConfig {
+            enable: true,
+            token: Some(String::new()),
+            ..HttpApiConfig::default()
+        };
+        assert!(matches!(
+            serve(service().await, config).await,
+            Err(HttpApiError::MissingToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized() {
+        for token in [None, Some(""), Some("wrong")] {
+            let (status, _) = send(
+                app().await,
+                request(Method::GET, "/todos", token, Value::Null),
+            )
+            .await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{token:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_errors() {
+        let app = app().await;
+        let token = Some("secret");
+
+        let (status, body) = send(
+            app.clone(),
+            request(
+                Method::PUT,
+                "/todos/1/message",
+                token,
+                json!({ "message": "Foo" }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"], "Todo item 1 not found");
+
+        let (status, body) = send(
+            app.clone(),
+            request(Method::POST, "/tags", token, json!({ "tag_name": " " })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["fields"][0]["field"], "tagName");
+
+        let (status, body) = send(
+            app.clone(),
+            request(Method::POST, "/tags", token, json!({ "tag_name": "work" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let tag_id = body.as_i64().unwrap();
+        let (status, _) = send(
+            app.clone(),
+            request(
+                Method::PUT,
+                &format!("/tags/{tag_id}/name"),
+                token,
+                json!({ "tag_name": "Work/Q4" }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (status, _) = send(
+            app.clone(),
+            request(
+                Method::DELETE,
+                "/tags",
+                token,
+                json!({ "tag_name": "Work/Q4" }),
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let (_, body) = send(app, request(Method::GET, "/tags", token, Value::Null)).await;
+        assert_eq!(body, json!([]));
+    }
+}
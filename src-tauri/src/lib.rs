@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+pub mod blob_store;
+pub mod config;
+pub mod database;
+pub mod http_api;
+pub mod service;
+pub mod todo_storage;
+pub mod util;
+
+/// `tauri.bundle.identifier` in `tauri.conf.json`, the app data directory is named after it
+pub const APP_IDENTIFIER: &str = "com.tauri.dev";
+
+/// the directory the gui and the cli keep their data in, the same one tauri resolves
+/// `app_data_dir` to, so the cli can find it without tauri
+pub fn app_data_dir() -> Option<PathBuf> {
+    dirs_next::data_dir().map(|dir| dir.join(APP_IDENTIFIER))
+}
+
+#[cfg(test)]
+mod test_lib {
+    use super::APP_IDENTIFIER;
+
+    #[test]
+    fn test_app_identifier() {
+        let conf: serde_json::Value =
+            serde_json::from_str(include_str!("../tauri.conf.json")).unwrap();
+        assert_eq!(conf["tauri"]["bundle"]["identifier"], APP_IDENTIFIER);
+    }
+}
@@ -3,40 +3,113 @@
     windows_subsystem = "windows"
 )]
 
-use database::init_sqlite;
-
-use crate::todo_storage::{
-    clean_tag, create_tag, delete_tag, delete_todo_item, edit_message, edit_priority, edit_tag,
-    fetch_all_tag_todo_item, fetch_all_tags, fetch_all_todo_item, get_tag_id, rename_tag,
-    save_full_todo_item, state_revert,
+use todo_list::{
+    app_data_dir, blob_store::BlobStore, config::AppConfig, database::init_sqlite, http_api,
+    service::TodoService, todo_storage,
 };
 
-mod database;
-mod todo_storage;
-mod util;
 #[tokio::main]
 async fn main() {
-    let pool = init_sqlite().await;
+    let config = AppConfig::load().expect("load config failure");
+    // the cli resolves the same directory, so both see the same attachments
+    let attachment_dir = config.attachment.dir_or(app_data_dir());
+    let service = TodoService::new(init_sqlite().await)
+        .with_tag_policy(config.tag)
+        .with_limits(config.limits)
+        .with_blob_store(BlobStore::new(attachment_dir));
+    report_tag_collisions(&service).await;
+
+    if config.http_api.enable {
+        let service = service.clone();
+        tokio::spawn(async move {
+            if let Err(err) = http_api::serve(service, config.http_api).await {
+                eprintln!("http api stopped: {err}");
+            }
+        });
+    }
+
     tauri::Builder::default()
-        .manage(pool)
+        .manage(service)
         .invoke_handler(tauri::generate_handler![
             // todo
-            save_full_todo_item,
-            fetch_all_todo_item,
-            edit_message,
-            edit_priority,
-            state_revert,
-            edit_tag,
-            clean_tag,
-            delete_todo_item,
+            todo_storage::save_full_todo_item,
+            todo_storage::quick_add,
+            todo_storage::fetch_all_todo_item,
+            todo_storage::edit_message,
+            todo_storage::edit_due,
+            todo_storage::edit_priority,
+            todo_storage::state_revert,
+            todo_storage::set_done,
+            todo_storage::edit_tag,
+            todo_storage::move_item,
+            todo_storage::fetch_notes,
+            todo_storage::edit_notes,
+            todo_storage::render_notes,
+            todo_storage::search_todo_item,
+            todo_storage::add_attachment,
+            todo_storage::fetch_attachments,
+            todo_storage::open_attachment,
+            todo_storage::remove_attachment,
+            todo_storage::add_comment,
+            todo_storage::edit_comment,
+            todo_storage::delete_comment,
+            todo_storage::fetch_comments,
+            todo_storage::fetch_activity,
+            todo_storage::add_dependency,
+            todo_storage::remove_dependency,
+            todo_storage::fetch_blockers,
+            todo_storage::start_timer,
+            todo_storage::stop_timer,
+            todo_storage::running_timer,
+            todo_storage::add_time_entry,
+            todo_storage::edit_time_entry,
+            todo_storage::delete_time_entry,
+            todo_storage::fetch_time_entries,
+            todo_storage::fetch_time_totals,
+            todo_storage::set_estimate,
+            todo_storage::fetch_estimate_by_tag,
+            todo_storage::fetch_estimate_by_priority,
+            todo_storage::fetch_stats,
+            todo_storage::create_saved_search,
+            todo_storage::edit_saved_search,
+            todo_storage::delete_saved_search,
+            todo_storage::fetch_saved_searches,
+            todo_storage::evaluate_saved_search,
+            todo_storage::clean_tag,
+            todo_storage::delete_todo_item,
+            todo_storage::bulk_edit,
             // tag
-            fetch_all_tags,
-            fetch_all_tag_todo_item,
-            rename_tag,
-            create_tag,
-            get_tag_id,
-            delete_tag
+            todo_storage::fetch_all_tags,
+            todo_storage::fetch_all_tag_todo_item,
+            todo_storage::rename_tag,
+            todo_storage::create_tag,
+            todo_storage::get_tag_id,
+            todo_storage::delete_tag,
+            todo_storage::fetch_tag_tree,
+            todo_storage::move_tag,
+            todo_storage::merge_tags,
+            todo_storage::update_tag_meta,
+            todo_storage::reorder_tags,
+            todo_storage::fetch_tag_stats,
+            todo_storage::prune_unused_tags,
+            todo_storage::suggest_tags
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+/// tags created before the tag policy may be the same tag now, they are kept
+/// and can be merged by the user
+async fn report_tag_collisions(service: &TodoService) {
+    match service.normalize_tags().await {
+        Ok(collisions) => {
+            for collision in collisions {
+                eprintln!(
+                    "tags {:?} are the same tag `{}`, merge them to clean up",
+                    collision.tags, collision.key
+                );
+            }
+        }
+        Err(err) => eprintln!("normalize tags failure: {err}"),
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::BTreeSet,
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::database::models::attachment::{AttachmentEntity, AttachmentModel};
+
+use super::{Attachment, OrNotFound, ServiceError, ServiceResult, TodoService};
+
+impl TodoService {
+    /// copy the file into the store and attach it to the item
+    pub async fn add_attachment(&self, item_id: i32, path: PathBuf) -> ServiceResult<Attachment> {
+        self.ensure_item(item_id).await?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("`{}` is not a file", path.display()),
+                )
+            })?;
+        let mime = mime_guess::from_path(&path)
+            .first_or_octet_stream()
+            .to_string();
+
+        let store = self.blob_store().clone();
+        let (hash, size) = blocking(move || store.put(&path)).await?;
+
+        let pool = self.pool();
+        let id = AttachmentEntity::save(pool, item_id, name, mime, size as i64, hash).await?;
+        let model = AttachmentEntity::find_by_id(pool, id).await?;
+        Ok(Self::to_attachment(model))
+    }
+
+    /// the attachments of the item, the earliest attached first
+    pub async fn fetch_attachments(&self, item_id: i32) -> ServiceResult<Vec<Attachment>> {
+        self.ensure_item(item_id).await?;
+        Ok(AttachmentEntity::fetch_all_by_item_id(self.pool(), item_id)
+            .await?
+            .into_iter()
+            .map(Self::to_attachment)
+            .collect())
+    }
+
+    /// copy the attachment out of the store with its name, so the other apps can open it
+    pub async fn export_attachment(&self, attachment_id: i32) -> ServiceResult<PathBuf> {
+        let AttachmentModel { name, hash, .. } =
+            AttachmentEntity::find_by_id(self.pool(), attachment_id)
+                .await
+                .or_not_found(|| ServiceError::AttachmentNotFound(attachment_id))?;
+        // only the last part of the name, it is not allowed to escape the export dir
+        let name = Path::new(&name)
+            .file_name()
+            .map_or_else(|| PathBuf::from("attachment"), PathBuf::from);
+        let target = std::env::temp_dir()
+            .join("todo-list-attachments")
+            .join(attachment_id.to_string())
+            .join(name);
+
+        let store = self.blob_store().clone();
+        let path = target.clone();
+        blocking(move || store.export(&hash, &path)).await?;
+        Ok(target)
+    }
+
+    pub async fn remove_attachment(&self, attachment_id: i32) -> ServiceResult<()> {
+        let pool = self.pool();
+        let AttachmentModel { hash, .. } = AttachmentEntity::find_by_id(pool, attachment_id)
+            .await
+            .or_not_found(|| ServiceError::AttachmentNotFound(attachment_id))?;
+        AttachmentEntity::remove(pool, attachment_id)
+            .await
+            .or_not_found(|| ServiceError::AttachmentNotFound(attachment_id))?;
+        self.remove_unused_blobs(vec![hash]).await
+    }
+
+    /// remove the blobs of the removed attachments, unless another attachment still use it
+    pub(super) async fn remove_unused_blobs(&self, hashes: Vec<String>) -> ServiceResult<()> {
+        if hashes.is_empty() {
+            return Ok(());
+        }
+        let used = AttachmentEntity::fetch_all_hash(self.pool())
+            .await?
+            .into_iter()
+            .collect::<BTreeSet<_>>();
+        let unused = hashes
+            .into_iter()
+            .filter(|hash| !used.contains(hash))
+            .collect::<Vec<_>>();
+
+        let store = self.blob_store().clone();
+        blocking(move || unused.iter().try_for_each(|hash| store.remove(hash))).await?;
+        Ok(())
+    }
+
+    fn to_attachment(
+        AttachmentModel {
+            id,
+            item_id,
+            name,
+            mime,
+            size,
+            created_at,
+            ..
+        }: AttachmentModel,
+    ) -> Attachment {
+        Attachment {
+            id,
+            item_id,
+            name,
+            mime,
+            size,
+            created_at,
+        }
+    }
+}
+
+/// the file operates block, run them out of the async runtime
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> io::Result<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+}
+
+#[cfg(test)]
+mod test_attachment {
+    use std::fs;
+
+    use crate::{
+        blob_store::BlobStore,
+        service::{
+            test_service::{item, service},
+            BulkOperate, BulkTarget,
+        },
+    };
+
+    #[tokio::test]
+    async fn test_attachment() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BlobStore::new(dir.path().join("blobs"));
+        let service = service().await.with_blob_store(store.clone());
+        let foo = item("Foo").save(&service).await;
+        let bar = item("Bar").save(&service).await;
+        let log = dir.path().join("run.log");
+        fs::write(&log, "failure").unwrap();
+
+        let a = service.add_attachment(foo, log.clone()).await.unwrap();
+        let b = service.add_attachment(bar, log.clone()).await.unwrap();
+        assert_eq!(
+            (a.name.as_str(), a.mime.as_str(), a.size),
+            ("run.log", "text/plain", 7)
+        );
+        assert_eq!(service.fetch_attachments(foo).await.unwrap(), [a]);
+
+        let exported = service.export_attachment(b.id).await.unwrap();
+        assert!(exported.ends_with("run.log"));
+        assert_eq!(fs::read(&exported).unwrap(), b"failure");
+
+        // the blob is shared by both items, removing one keeps it
+        let (hash, _) = store.put(&log).unwrap();
+        service.delete_todo_item(foo).await.unwrap();
+        assert!(store.path(&hash).exists());
+        service.remove_attachment(b.id).await.unwrap();
+        assert!(!store.path(&hash).exists());
+        assert!(service.fetch_attachments(bar).await.unwrap().is_empty());
+
+        // the bulk delete collects the blobs too
+        service.add_attachment(bar, log.clone()).await.unwrap();
+        service
+            .bulk_edit(BulkTarget::Ids(vec![bar]), BulkOperate::Delete)
+            .await
+            .unwrap();
+        assert!(!store.path(&hash).exists());
+
+        let missing = b.id + 10;
+        assert!(service
+            .remove_attachment(missing)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert!(service
+            .add_attachment(foo, log)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use sqlx::SqliteConnection;
+
+use crate::database::models::attachment::AttachmentEntity;
+use crate::database::models::comment::CommentEntity;
+use crate::database::models::dependency::DependencyEntity;
+use crate::database::models::item_event::{ItemChange, ItemEventEntity};
+use crate::database::models::tag_item_bind::BindEntity;
+use crate::database::models::tags::TagEntity;
+use crate::database::models::time_entry::TimeEntryEntity;
+use crate::database::models::todo_item::TodoItemEntity;
+
+use super::{
+    validate::Validator, BulkOperate, BulkResult, BulkTarget, ItemFilter, ServiceError,
+    ServiceResult, TodoService,
+};
+
+impl TodoService {
+    /// apply the operate on all the target items in one transaction
+    ///
+    /// the result of every target id is returned, an id without item is reported
+    /// as not found and the others are still applied. a database error rollback all
+    pub async fn bulk_edit(
+        &self,
+        target: BulkTarget,
+        operate: BulkOperate,
+    ) -> ServiceResult<Vec<BulkResult>> {
+        if let BulkOperate::AddTags(tags) = &operate {
+            Validator::new(self.limits()).tags("tags", tags).finish()?;
+        }
+        let targets = match target {
+            BulkTarget::Ids(ids) => ids,
+            BulkTarget::Filter(filter) => self.filter_item_ids(filter).await?,
+        };
+
+        let mut unused_hashes = vec![];
+        let mut tx = self.pool().begin().await?;
+        let models = TodoItemEntity::find_all_by_id(&mut tx, targets.iter().copied()).await?;
+        let exist = models.iter().map(|model| model.id).collect::<BTreeSet<_>>();
+        let mut blocked = BTreeMap::new();
+
+        match operate {
+            BulkOperate::SetDone(done) => {
+                if done {
+                    blocked = Self::fetch_blocked_targets(&mut tx, &exist).await?;
+                }
+                let ids = exist.iter().filter(|id| !blocked.contains_key(id));
+                TodoItemEntity::update_done_all(&mut tx, ids.copied(), done).await?;
+                let changes = models
+                    .iter()
+                    .filter(|model| model.done != done && !blocked.contains_key(&model.id))
+                    .map(|model| (model.id, ItemChange::Done(done)))
+                    .collect::<Vec<_>>();
+                ItemEventEntity::save_all(&mut tx, changes).await?;
+            }
+            BulkOperate::SetPriority(priority) => {
+                let priority = priority.into();
+                TodoItemEntity::update_priority_all(&mut tx, exist.iter().copied(), priority)
+                    .await?;
+                let changes = models
+                    .iter()
+                    .filter(|model| model.priority != priority)
+                    .map(|model| {
+                        let change = ItemChange::Priority {
+                            from: model.priority,
+                            to: priority,
+                        };
+                        (model.id, change)
+                    })
+                    .collect::<Vec<_>>();
+                ItemEventEntity::save_all(&mut tx, changes).await?;
+            }
+            BulkOperate::AddTags(tags) => {
+                for tag in tags {
+                    let tag_id = TagEntity::save_in(&mut tx, self.tag_policy(), &tag).await?;
+                    BindEntity::bind_items(&mut tx, tag_id, exist.iter().copied()).await?;
+                }
+            }
+            BulkOperate::RemoveTags(tags) => {
+                // the tags not exist are bind with nothing, no need to create them
+                for tag in tags {
+                    if let Some(tag_id) =
+                        TagEntity::find_id(&mut *tx, self.tag_policy(), &tag).await?
+                    {
+                        BindEntity::unbind_items(&mut tx, tag_id, exist.iter().copied()).await?;
+                    }
+                }
+            }
+            BulkOperate::Delete => {
+                BindEntity::remove_bind_item_ids(&mut tx, exist.iter().copied()).await?;
+                unused_hashes =
+                    AttachmentEntity::fetch_all_hash_by_item_id(&mut tx, exist.iter().copied())
+                        .await?;
+                AttachmentEntity::remove_all_by_item_id(&mut tx, exist.iter().copied()).await?;
+                CommentEntity::remove_all_by_item_id(&mut tx, exist.iter().copied()).await?;
+                ItemEventEntity::remove_all_by_item_id(&mut tx, exist.iter().copied()).await?;
+                DependencyEntity::remove_all_by_item_id(&mut tx, exist.iter().copied()).await?;
+                TimeEntryEntity::remove_all_by_item_id(&mut tx, exist.iter().copied()).await?;
+                TodoItemEntity::remove_all(&mut tx, exist.iter().copied()).await?;
+            }
+        }
+        tx.commit().await?;
+        self.remove_unused_blobs(unused_hashes).await?;
+
+        Ok(targets
+            .into_iter()
+            .map(|item_id| {
+                let error = if !exist.contains(&item_id) {
+                    Some(format!("Todo item {item_id} not found"))
+                } else {
+                    blocked.get(&item_id).map(|blockers| {
+                        let blockers = blockers.clone();
+                        ServiceError::ItemBlocked { item_id, blockers }.to_string()
+                    })
+                };
+                BulkResult { item_id, error }
+            })
+            .collect())
+    }
+
+    /// the targets can not be done, with their open blockers. a blocker done in the same
+    /// operate does not count, unless it is blocked itself
+    async fn fetch_blocked_targets(
+        tx: &mut SqliteConnection,
+        targets: &BTreeSet<i32>,
+    ) -> ServiceResult<BTreeMap<i32, Vec<i32>>> {
+        let open = Self::fetch_open_blockers(tx, targets.iter().copied().collect()).await?;
+        let mut blocked = BTreeMap::new();
+        loop {
+            let found = open
+                .iter()
+                .filter(|(item_id, _)| !blocked.contains_key(*item_id))
+                .filter(|(_, blockers)| {
+                    blockers
+                        .iter()
+                        .any(|id| !targets.contains(id) || blocked.contains_key(id))
+                })
+                .map(|(item_id, blockers)| (*item_id, blockers.clone()))
+                .collect::<Vec<_>>();
+            if found.is_empty() {
+                return Ok(blocked);
+            }
+            blocked.extend(found);
+        }
+    }
+
+    async fn filter_item_ids(
+        &self,
+        ItemFilter {
+            tag_id,
+            include_descendants,
+            done,
+        }: ItemFilter,
+    ) -> ServiceResult<Vec<i32>> {
+        let pool = self.pool();
+        let items = match tag_id {
+            Some(tag_id) => {
+                let mut tag_ids = vec![tag_id];
+                if include_descendants {
+                    tag_ids.extend(TagEntity::fetch_descendants(pool, tag_id).await?);
+                }
+                let mut item_ids = BTreeSet::new();
+                for tag_id in tag_ids {
+                    item_ids.extend(BindEntity::fetch_all_item_id(pool, tag_id).await?);
+                }
+                TodoItemEntity::find_all_by_id(pool, item_ids).await?
+            }
+            None => TodoItemEntity::fetch_all(pool, None).await?,
+        };
+
+        Ok(items
+            .into_iter()
+            .filter(|model| match done {
+                Some(done) => model.done == done,
+                None => true,
+            })
+            .map(|model| model.id)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test_bulk {
+    use crate::service::{
+        test_service::{item, service},
+        BulkOperate, BulkResult, BulkTarget, ItemFilter, PriorityLevel, ServiceError,
+        ValidationError,
+    };
+
+    #[tokio::test]
+    async fn test_bulk_ids() {
+        let service = service().await;
+        let foo = item("Foo").tag("a").save(&service).await;
+        let bar = item("Bar").save(&service).await;
+        let missing = bar + 1;
+
+        let results = service
+            .bulk_edit(
+                BulkTarget::Ids(vec![foo, missing, bar]),
+                BulkOperate::SetDone(true),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            results,
+            vec![
+                BulkResult {
+                    item_id: foo,
+                    error: None
+                },
+                BulkResult {
+                    item_id: missing,
+                    error: Some(format!("Todo item {missing} not found"))
+                },
+                BulkResult {
+                    item_id: bar,
+                    error: None
+                },
+            ]
+        );
+
+        service
+            .bulk_edit(
+                BulkTarget::Ids(vec![foo, bar]),
+                BulkOperate::AddTags(vec!["a".into(), "b".into()]),
+            )
+            .await
+            .unwrap();
+        service
+            .bulk_edit(
+                BulkTarget::Ids(vec![foo, bar]),
+                BulkOperate::SetPriority(PriorityLevel::High),
+            )
+            .await
+            .unwrap();
+        let expect = |message: &str| {
+            item(message)
+                .priority(PriorityLevel::High)
+                .done()
+                .tags(&["a", "b"])
+                .build()
+        };
+        let mut items = service.fetch_all_todo_item().await.unwrap();
+        items.iter_mut().for_each(|(_, item)| item.tags.sort());
+        assert_eq!(items, vec![(foo, expect("Foo")), (bar, expect("Bar"))]);
+
+        service
+            .bulk_edit(
+                BulkTarget::Ids(vec![foo, bar]),
+                BulkOperate::RemoveTags(vec!["a".into()]),
+            )
+            .await
+            .unwrap();
+        let items = service.fetch_all_todo_item().await.unwrap();
+        assert!(items.iter().all(|(_, item)| item.tags == ["b"]));
+
+        // remove a tag not exist does not create it
+        service
+            .bulk_edit(
+                BulkTarget::Ids(vec![foo, bar]),
+                BulkOperate::RemoveTags(vec!["c".into()]),
+            )
+            .await
+            .unwrap();
+        let tags = service.fetch_all_tags().await.unwrap();
+        assert!(tags.iter().all(|(_, tag, _)| tag != "c"));
+
+        assert!(matches!(
+            service
+                .bulk_edit(
+                    BulkTarget::Ids(vec![foo, bar]),
+                    BulkOperate::AddTags(vec!["d".into(), " ".into()]),
+                )
+                .await,
+            Err(ServiceError::Validation(ValidationError(fields))) if fields[0].field == "tags[1]"
+        ));
+        let tags = service.fetch_all_tags().await.unwrap();
+        assert!(tags.iter().all(|(_, tag, _)| tag != "d"));
+
+        service
+            .bulk_edit(BulkTarget::Ids(vec![foo, bar]), BulkOperate::Delete)
+            .await
+            .unwrap();
+        assert!(service.fetch_all_todo_item().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_filter() {
+        let service = service().await;
+        let foo = item("Foo").tag("a").save(&service).await;
+        let bar = item("Bar").tag("a").save(&service).await;
+        item("Baz").tag("b").save(&service).await;
+        service.revert_done(bar).await.unwrap();
+        let a = service.get_tag_id("a".into()).await.unwrap();
+
+        let results = service
+            .bulk_edit(
+                BulkTarget::Filter(ItemFilter {
+                    tag_id: Some(a),
+                    done: Some(false),
+                    ..ItemFilter::default()
+                }),
+                BulkOperate::SetDone(true),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            results,
+            vec![BulkResult {
+                item_id: foo,
+                error: None
+            }]
+        );
+        let done = service
+            .fetch_all_todo_item()
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|(_, item)| item.done)
+            .count();
+        assert_eq!(done, 2);
+    }
+}
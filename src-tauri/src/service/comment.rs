@@ -0,0 +1,205 @@
+use crate::database::models::{
+    comment::{CommentEntity, CommentModel},
+    item_event::{ItemChange, ItemEventEntity},
+};
+
+use super::{
+    validate::Validator, Activity, Comment, OrNotFound, ServiceError, ServiceResult, TodoService,
+};
+
+impl TodoService {
+    pub async fn add_comment(
+        &self,
+        item_id: i32,
+        author: String,
+        body: String,
+    ) -> ServiceResult<Comment> {
+        Validator::new(self.limits())
+            .tag("author", &author)
+            .message("body", &body)
+            .finish()?;
+        self.ensure_item(item_id).await?;
+
+        let pool = self.pool();
+        let id = CommentEntity::save(pool, item_id, author, body).await?;
+        let model = CommentEntity::find_by_id(pool, id).await?;
+        Ok(Self::to_comment(model))
+    }
+
+    pub async fn edit_comment(&self, comment_id: i32, body: String) -> ServiceResult<()> {
+        Validator::new(self.limits())
+            .message("body", &body)
+            .finish()?;
+        CommentEntity::update_body(self.pool(), comment_id, body)
+            .await
+            .or_not_found(|| ServiceError::CommentNotFound(comment_id))
+    }
+
+    pub async fn delete_comment(&self, comment_id: i32) -> ServiceResult<()> {
+        CommentEntity::remove(self.pool(), comment_id)
+            .await
+            .or_not_found(|| ServiceError::CommentNotFound(comment_id))
+    }
+
+    /// the comments on the item, the earliest first
+    pub async fn fetch_comments(&self, item_id: i32) -> ServiceResult<Vec<Comment>> {
+        self.ensure_item(item_id).await?;
+        Ok(CommentEntity::fetch_all_by_item_id(self.pool(), item_id)
+            .await?
+            .into_iter()
+            .map(Self::to_comment)
+            .collect())
+    }
+
+    /// the comments and the changes of the item interleaved, the earliest first
+    pub async fn fetch_activity(&self, item_id: i32) -> ServiceResult<Vec<Activity>> {
+        let comments = self.fetch_comments(item_id).await?;
+        let changes = ItemEventEntity::fetch_all_by_item_id(self.pool(), item_id)
+            .await?
+            .into_iter()
+            .filter_map(|event| {
+                let at = event.happened_at;
+                event.change().map(|change| match change {
+                    ItemChange::Priority { from, to } => Activity::PriorityChanged {
+                        from: from.into(),
+                        to: to.into(),
+                        at,
+                    },
+                    ItemChange::Done(done) => Activity::DoneChanged { done, at },
+                })
+            });
+
+        // both are sorted by time already, the sort is stable so a comment goes before
+        // a change happened at the same time
+        let mut activity = comments
+            .into_iter()
+            .map(Activity::Comment)
+            .chain(changes)
+            .collect::<Vec<_>>();
+        activity.sort_by_key(|activity| activity.at());
+        Ok(activity)
+    }
+
+    fn to_comment(
+        CommentModel {
+            id,
+            item_id,
+            author,
+            body,
+            created_at,
+            edited,
+        }: CommentModel,
+    ) -> Comment {
+        Comment {
+            id,
+            item_id,
+            author,
+            body,
+            created_at,
+            edited,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_comment {
+    use crate::service::{
+        test_service::{item, service},
+        Activity, BulkOperate, BulkTarget, PriorityLevel, ServiceError,
+    };
+
+    #[tokio::test]
+    async fn test_comment() {
+        let service = service().await;
+        let foo = item("Foo").save(&service).await;
+
+        let a = service
+            .add_comment(foo, "amy".into(), "looks hard".into())
+            .await
+            .unwrap();
+        assert_eq!((a.author.as_str(), a.edited), ("amy", false));
+        service
+            .edit_comment(a.id, "looks easy".into())
+            .await
+            .unwrap();
+        let b = service
+            .add_comment(foo, "bob".into(), "on it".into())
+            .await
+            .unwrap();
+
+        let comments = service.fetch_comments(foo).await.unwrap();
+        assert_eq!(
+            comments
+                .iter()
+                .map(|comment| (comment.body.as_str(), comment.edited))
+                .collect::<Vec<_>>(),
+            [("looks easy", true), ("on it", false)]
+        );
+
+        service.delete_comment(b.id).await.unwrap();
+        assert!(service
+            .delete_comment(b.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert!(service
+            .edit_comment(b.id, "gone".into())
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert!(matches!(
+            service.add_comment(foo, "".into(), "empty".into()).await,
+            Err(ServiceError::Validation(_))
+        ));
+
+        service.delete_todo_item(foo).await.unwrap();
+        assert!(service
+            .add_comment(foo, "amy".into(), "late".into())
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_activity() {
+        let service = service().await;
+        let foo = item("Foo").save(&service).await;
+
+        service
+            .add_comment(foo, "amy".into(), "urgent".into())
+            .await
+            .unwrap();
+        service
+            .edit_priority(foo, PriorityLevel::High)
+            .await
+            .unwrap();
+        // the same priority is not a change
+        service
+            .edit_priority(foo, PriorityLevel::High)
+            .await
+            .unwrap();
+        service.set_done(foo, true).await.unwrap();
+        service
+            .bulk_edit(BulkTarget::Ids(vec![foo]), BulkOperate::SetDone(false))
+            .await
+            .unwrap();
+
+        let activity = service.fetch_activity(foo).await.unwrap();
+        assert!(matches!(activity[0], Activity::Comment(ref comment) if comment.body == "urgent"));
+        assert!(matches!(
+            activity[1],
+            Activity::PriorityChanged {
+                from: PriorityLevel::Medium,
+                to: PriorityLevel::High,
+                ..
+            }
+        ));
+        assert!(matches!(
+            activity[2..],
+            [
+                Activity::DoneChanged { done: true, .. },
+                Activity::DoneChanged { done: false, .. }
+            ]
+        ));
+    }
+}
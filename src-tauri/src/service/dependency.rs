@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use sqlx::{Executor, Sqlite};
+
+use crate::database::models::{
+    dependency::{DependencyEntity, DependencyModel},
+    todo_item::TodoItemEntity,
+};
+
+use super::{
+    FieldError, OrNotFound, ServiceError, ServiceResult, TodoItem, TodoService, ValidationError,
+};
+
+impl TodoService {
+    /// the item can not be done before the blocker is done. adding it twice changes nothing
+    pub async fn add_dependency(&self, item_id: i32, blocker_id: i32) -> ServiceResult<()> {
+        if item_id == blocker_id {
+            return Err(ValidationError(vec![FieldError {
+                field: "blockerId".into(),
+                message: "must not be the item itself".into(),
+            }])
+            .into());
+        }
+
+        let mut tx = self.pool().begin().await?;
+        let exist = TodoItemEntity::find_all_by_id(&mut tx, [item_id, blocker_id])
+            .await?
+            .into_iter()
+            .map(|model| model.id)
+            .collect::<BTreeSet<_>>();
+        if let Some(&missing) = [item_id, blocker_id].iter().find(|id| !exist.contains(id)) {
+            return Err(ServiceError::ItemNotFound(missing));
+        }
+
+        // walk the blockers from the new blocker, the item must not be found on the way
+        let mut blockers = HashMap::<i32, Vec<i32>>::new();
+        for DependencyModel {
+            item_id,
+            blocker_id,
+        } in DependencyEntity::fetch_all(&mut tx).await?
+        {
+            blockers.entry(item_id).or_default().push(blocker_id);
+        }
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![blocker_id];
+        while let Some(current) = stack.pop() {
+            if current == item_id {
+                return Err(ServiceError::DependencyCycle {
+                    item_id,
+                    blocker_id,
+                });
+            }
+            if visited.insert(current) {
+                stack.extend(blockers.get(&current).into_iter().flatten());
+            }
+        }
+
+        let dependency = DependencyModel {
+            item_id,
+            blocker_id,
+        };
+        DependencyEntity::save(&mut tx, dependency).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn remove_dependency(&self, item_id: i32, blocker_id: i32) -> ServiceResult<()> {
+        let dependency = DependencyModel {
+            item_id,
+            blocker_id,
+        };
+        DependencyEntity::remove(self.pool(), dependency)
+            .await
+            .or_not_found(|| ServiceError::DependencyNotFound {
+                item_id,
+                blocker_id,
+            })
+    }
+
+    /// the items the item waits for, done or not
+    pub async fn fetch_blockers(&self, item_id: i32) -> ServiceResult<Vec<(i32, TodoItem)>> {
+        self.ensure_item(item_id).await?;
+        let pool = self.pool();
+        let ids = DependencyEntity::fetch_all_blocker_id(pool, item_id).await?;
+        let models = TodoItemEntity::find_all_by_id(pool, ids).await?;
+        self.with_tags(models).await
+    }
+
+    /// the items having any blocker not done yet
+    pub(super) async fn fetch_blocked_ids(&self) -> ServiceResult<BTreeSet<i32>> {
+        Ok(DependencyEntity::fetch_all_open(self.pool(), None)
+            .await?
+            .into_iter()
+            .map(|dependency| dependency.item_id)
+            .collect())
+    }
+
+    /// the open blockers of the items, by the item. only the items blocked are included
+    pub(super) async fn fetch_open_blockers<'e, E: Executor<'e, Database = Sqlite>>(
+        executor: E,
+        item_ids: Vec<i32>,
+    ) -> ServiceResult<BTreeMap<i32, Vec<i32>>> {
+        let mut blockers = BTreeMap::<i32, Vec<i32>>::new();
+        for DependencyModel {
+            item_id,
+            blocker_id,
+        } in DependencyEntity::fetch_all_open(executor, Some(item_ids)).await?
+        {
+            blockers.entry(item_id).or_default().push(blocker_id);
+        }
+        Ok(blockers)
+    }
+}
+
+#[cfg(test)]
+mod test_dependency {
+    use crate::service::{
+        test_service::{item, service},
+        BulkOperate, BulkResult, BulkTarget, ServiceError, TodoItem,
+    };
+
+    #[tokio::test]
+    async fn test_cycle() {
+        let service = service().await;
+        let foo = item("Foo").save(&service).await;
+        let bar = item("Bar").save(&service).await;
+        let baz = item("Baz").save(&service).await;
+
+        service.add_dependency(foo, bar).await.unwrap();
+        service.add_dependency(bar, baz).await.unwrap();
+        // adding it again is fine
+        service.add_dependency(foo, bar).await.unwrap();
+        assert!(matches!(
+            service.add_dependency(baz, foo).await,
+            Err(ServiceError::DependencyCycle {
+                item_id,
+                blocker_id
+            }) if (item_id, blocker_id) == (baz, foo)
+        ));
+        assert!(matches!(
+            service.add_dependency(foo, foo).await,
+            Err(ServiceError::Validation(_))
+        ));
+        assert!(service
+            .add_dependency(foo, baz + 1)
+            .await
+            .unwrap_err()
+            .is_not_found());
+
+        service.remove_dependency(bar, baz).await.unwrap();
+        assert!(service
+            .remove_dependency(bar, baz)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        service.add_dependency(baz, foo).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_blocked() {
+        let service = service().await;
+        let foo = item("Foo").save(&service).await;
+        let bar = item("Bar").save(&service).await;
+        service.add_dependency(foo, bar).await.unwrap();
+
+        let blocked = |items: Vec<(i32, TodoItem)>| {
+            items
+                .into_iter()
+                .filter(|(_, item)| item.blocked)
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(blocked(service.fetch_all_todo_item().await.unwrap()), [foo]);
+        assert_eq!(
+            service.fetch_blockers(foo).await.unwrap(),
+            [(bar, item("Bar").build())]
+        );
+
+        // the blocked item can not be done, reopen it is fine
+        assert!(matches!(
+            service.revert_done(foo).await,
+            Err(ServiceError::ItemBlocked { item_id, .. }) if item_id == foo
+        ));
+        assert!(matches!(
+            service.set_done(foo, true).await,
+            Err(ServiceError::ItemBlocked { .. })
+        ));
+        service.set_done(foo, false).await.unwrap();
+        let results = service
+            .bulk_edit(BulkTarget::Ids(vec![foo]), BulkOperate::SetDone(true))
+            .await
+            .unwrap();
+        assert!(results[0].error.is_some());
+
+        // done together with the blocker is fine
+        let results = service
+            .bulk_edit(BulkTarget::Ids(vec![foo, bar]), BulkOperate::SetDone(true))
+            .await
+            .unwrap();
+        assert!(results
+            .iter()
+            .all(|BulkResult { error, .. }| error.is_none()));
+        assert!(blocked(service.fetch_all_todo_item().await.unwrap()).is_empty());
+
+        service.set_done(bar, false).await.unwrap();
+        service.delete_todo_item(bar).await.unwrap();
+        assert!(blocked(service.fetch_all_todo_item().await.unwrap()).is_empty());
+        assert!(service.fetch_blockers(foo).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::database::models::{
+    tag_item_bind::{BindEntity, BindEstimateSum},
+    tags::{TagEntity, TagModel},
+    todo_item::{self, TodoItemEntity},
+};
+
+use super::{
+    Estimate, EstimateRollup, EstimateSum, OrNotFound, PriorityLevel, ServiceError, ServiceResult,
+    Tag, TodoService,
+};
+
+impl TodoService {
+    /// set or clear the estimate of the item
+    pub async fn set_estimate(
+        &self,
+        item_id: i32,
+        estimate: Option<Estimate>,
+    ) -> ServiceResult<()> {
+        let (minutes, points) = to_columns(estimate);
+        TodoItemEntity::update_estimate(self.pool(), item_id, minutes, points)
+            .await
+            .or_not_found(|| ServiceError::ItemNotFound(item_id))
+    }
+
+    /// the estimates of the items bind with every tag, the tags without item included
+    pub async fn fetch_estimate_by_tag(&self) -> ServiceResult<Vec<EstimateRollup<Tag>>> {
+        let pool = self.pool();
+        let mut rollups = TagEntity::fetch_all(pool, None)
+            .await?
+            .into_iter()
+            .map(|TagModel { id, value, .. }| (id, rollup(value)))
+            .collect::<Vec<_>>();
+
+        for BindEstimateSum {
+            tag_id,
+            done,
+            items,
+            estimated,
+            minutes,
+            points,
+        } in BindEntity::sum_estimate_by_done(pool).await?
+        {
+            if let Some((_, rollup)) = rollups.iter_mut().find(|(id, _)| *id == tag_id) {
+                let sum = if done {
+                    &mut rollup.done
+                } else {
+                    &mut rollup.open
+                };
+                *sum = to_sum(items, estimated, minutes, points);
+            }
+        }
+        Ok(rollups.into_iter().map(|(_, rollup)| rollup).collect())
+    }
+
+    /// the estimates of the items in every priority, the highest priority first
+    pub async fn fetch_estimate_by_priority(
+        &self,
+    ) -> ServiceResult<Vec<EstimateRollup<PriorityLevel>>> {
+        let mut rollups = [
+            PriorityLevel::VeryHigh,
+            PriorityLevel::High,
+            PriorityLevel::Medium,
+            PriorityLevel::Low,
+            PriorityLevel::VeryLow,
+        ]
+        .into_iter()
+        .map(rollup)
+        .collect::<Vec<_>>();
+
+        for todo_item::EstimateSum {
+            priority,
+            done,
+            items,
+            estimated,
+            minutes,
+            points,
+        } in TodoItemEntity::sum_estimate_by_priority(self.pool()).await?
+        {
+            let level = PriorityLevel::from(priority);
+            if let Some(rollup) = rollups.iter_mut().find(|rollup| rollup.key == level) {
+                let sum = if done {
+                    &mut rollup.done
+                } else {
+                    &mut rollup.open
+                };
+                *sum = to_sum(items, estimated, minutes, points);
+            }
+        }
+        Ok(rollups)
+    }
+}
+
+/// the estimate stored in the columns, the minutes win if both are set
+pub(super) fn from_columns(minutes: Option<i64>, points: Option<i64>) -> Option<Estimate> {
+    let minutes = minutes.map(|minutes| Estimate::Minutes(minutes as u32));
+    minutes.or_else(|| points.map(|points| Estimate::Points(points as u32)))
+}
+
+pub(super) fn to_columns(estimate: Option<Estimate>) -> (Option<i64>, Option<i64>) {
+    match estimate {
+        Some(Estimate::Minutes(minutes)) => (Some(minutes.into()), None),
+        Some(Estimate::Points(points)) => (None, Some(points.into())),
+        None => (None, None),
+    }
+}
+
+fn rollup<K>(key: K) -> EstimateRollup<K> {
+    EstimateRollup {
+        key,
+        open: EstimateSum::default(),
+        done: EstimateSum::default(),
+    }
+}
+
+fn to_sum(items: i32, estimated: i32, minutes: Option<i64>, points: Option<i64>) -> EstimateSum {
+    EstimateSum {
+        items,
+        estimated,
+        minutes: minutes.unwrap_or_default(),
+        points: points.unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod test_estimate {
+    use crate::service::{
+        test_service::{item, service},
+        Estimate, EstimateSum, PriorityLevel,
+    };
+
+    #[tokio::test]
+    async fn test_estimate() {
+        let service = service().await;
+        let foo = item("Foo")
+            .priority(PriorityLevel::High)
+            .estimate(Estimate::Minutes(90))
+            .tag("sprint")
+            .save(&service)
+            .await;
+        let bar = item("Bar")
+            .priority(PriorityLevel::High)
+            .tag("sprint")
+            .save(&service)
+            .await;
+        service
+            .set_estimate(bar, Some(Estimate::Points(5)))
+            .await
+            .unwrap();
+        let baz = item("Baz")
+            .priority(PriorityLevel::Low)
+            .estimate(Estimate::Points(2))
+            .tag("sprint")
+            .save(&service)
+            .await;
+        service.set_done(baz, true).await.unwrap();
+
+        let items = service.fetch_all_todo_item().await.unwrap();
+        assert_eq!(
+            items[0],
+            (
+                foo,
+                item("Foo")
+                    .priority(PriorityLevel::High)
+                    .estimate(Estimate::Minutes(90))
+                    .tag("sprint")
+                    .build()
+            )
+        );
+        assert_eq!(items[1].1.estimate, Some(Estimate::Points(5)));
+
+        let by_tag = service.fetch_estimate_by_tag().await.unwrap();
+        assert_eq!(by_tag.len(), 1);
+        assert_eq!(
+            (&by_tag[0].open, &by_tag[0].done),
+            (
+                &EstimateSum {
+                    items: 2,
+                    estimated: 2,
+                    minutes: 90,
+                    points: 5
+                },
+                &EstimateSum {
+                    items: 1,
+                    estimated: 1,
+                    minutes: 0,
+                    points: 2
+                }
+            )
+        );
+
+        service.set_estimate(foo, None).await.unwrap();
+        let by_priority = service.fetch_estimate_by_priority().await.unwrap();
+        assert_eq!(by_priority.len(), 5);
+        assert_eq!(by_priority[1].key, PriorityLevel::High);
+        assert_eq!(
+            by_priority[1].open,
+            EstimateSum {
+                items: 2,
+                estimated: 1,
+                minutes: 0,
+                points: 5
+            }
+        );
+        assert_eq!(by_priority[3].done.points, 2);
+        assert_eq!(by_priority[0].open, EstimateSum::default());
+
+        assert!(service
+            .set_estimate(baz + 1, None)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+}
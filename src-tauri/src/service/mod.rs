@@ -0,0 +1,531 @@
+use std::io;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::{
+    blob_store::BlobStore,
+    config::{AttachmentConfig, InputLimits, TagPolicy},
+    database::models::tags::{TagNameError, TagTreeError},
+};
+
+pub mod attachment;
+pub mod bulk;
+pub mod comment;
+pub mod dependency;
+pub mod estimate;
+pub mod notes;
+pub mod quick_add;
+pub mod saved_search;
+pub mod stats;
+pub mod tag;
+#[cfg(test)]
+mod test_model;
+pub mod time_entry;
+pub mod todo_item;
+pub mod validate;
+
+pub use validate::{FieldError, ValidationError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ServiceError {
+    #[error("Todo item {0} not found")]
+    ItemNotFound(i32),
+    #[error("Tag {0} not found")]
+    TagNotFound(i32),
+    #[error("Tag `{0}` not found")]
+    TagNameNotFound(Tag),
+    #[error("Attachment {0} not found")]
+    AttachmentNotFound(i32),
+    #[error("Comment {0} not found")]
+    CommentNotFound(i32),
+    #[error("Time entry {0} not found")]
+    TimeEntryNotFound(i32),
+    #[error("Saved search {0} not found")]
+    SavedSearchNotFound(i32),
+    #[error("Todo item {item_id} is not blocked by item {blocker_id}")]
+    DependencyNotFound { item_id: i32, blocker_id: i32 },
+    #[error("Item {blocker_id} blocking item {item_id} will make a cycle")]
+    DependencyCycle { item_id: i32, blocker_id: i32 },
+    #[error("Todo item {item_id} is blocked by the open items {blockers:?}")]
+    ItemBlocked { item_id: i32, blockers: Vec<i32> },
+    #[error("Attachment store failure: {0}")]
+    BlobStore(#[from] io::Error),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error(transparent)]
+    TagTree(TagTreeError),
+    #[error(transparent)]
+    TagName(#[from] TagNameError),
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+}
+
+pub type ServiceResult<T> = Result<T, ServiceError>;
+
+impl ServiceError {
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            Self::ItemNotFound(_)
+                | Self::TagNotFound(_)
+                | Self::TagNameNotFound(_)
+                | Self::AttachmentNotFound(_)
+                | Self::CommentNotFound(_)
+                | Self::TimeEntryNotFound(_)
+                | Self::SavedSearchNotFound(_)
+                | Self::DependencyNotFound { .. }
+        )
+    }
+}
+
+/// turn the [`sqlx::Error::RowNotFound`] of the entities into the not found error of the service
+trait OrNotFound<T> {
+    fn or_not_found(self, not_found: impl FnOnce() -> ServiceError) -> ServiceResult<T>;
+}
+
+impl From<TagTreeError> for ServiceError {
+    fn from(err: TagTreeError) -> Self {
+        match err {
+            TagTreeError::NotFound(tag_id) => Self::TagNotFound(tag_id),
+            TagTreeError::Database(err) => Self::Database(err),
+            err => Self::TagTree(err),
+        }
+    }
+}
+
+impl<T> OrNotFound<T> for Result<T, sqlx::Error> {
+    fn or_not_found(self, not_found: impl FnOnce() -> ServiceError) -> ServiceResult<T> {
+        self.map_err(|err| match err {
+            sqlx::Error::RowNotFound => not_found(),
+            err => err.into(),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TodoItem {
+    pub message: String,
+    pub priority: PriorityLevel,
+    pub done: bool,
+    /// some blocker of the item is not done yet, computed on fetch and ignored on save
+    #[serde(default)]
+    pub blocked: bool,
+    #[serde(default)]
+    pub estimate: Option<Estimate>,
+    /// the day the item should be done by
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    pub tags: Vec<Tag>,
+}
+
+/// how big the item is, in the unit the team plans with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "unit", content = "value")]
+pub enum Estimate {
+    Minutes(u32),
+    Points(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PriorityLevel {
+    VeryHigh,
+    High,
+    Medium,
+    Low,
+    VeryLow,
+}
+
+pub type Tag = String;
+
+/// the display information of a tag, shared by all the machines using this database
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagMeta {
+    /// hex color like `#ff8800`
+    pub color: Option<String>,
+    /// emoji or icon name
+    pub icon: Option<String>,
+    pub description: Option<String>,
+    /// manual sort position of the tag
+    #[serde(default)]
+    pub position: i32,
+}
+
+/// how many items use the tag
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagStats {
+    pub id: i32,
+    pub value: Tag,
+    pub open: i32,
+    pub done: i32,
+}
+
+/// a tag with its place in the tag hierarchy
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagNode {
+    pub id: i32,
+    pub value: Tag,
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum EditMode {
+    Add,
+    Remove,
+}
+
+/// the done state after [`TodoService::set_done`], no row affected means the item not exist
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DoneState {
+    pub done: bool,
+    pub rows_affected: u64,
+}
+
+/// the items a bulk operate apply on
+#[derive(Debug, Serialize, Deserialize)]
+pub enum BulkTarget {
+    Ids(Vec<i32>),
+    Filter(ItemFilter),
+}
+
+/// select the items by their tag and state, every field narrow the selection
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemFilter {
+    pub tag_id: Option<i32>,
+    /// with `tag_id`, the items bind with any descendant of the tag are selected too
+    pub include_descendants: bool,
+    pub done: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum BulkOperate {
+    SetDone(bool),
+    SetPriority(PriorityLevel),
+    AddTags(Vec<Tag>),
+    RemoveTags(Vec<Tag>),
+    Delete,
+}
+
+/// the result of the bulk operate on one item, `error` is none when it succeed
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BulkResult {
+    pub item_id: i32,
+    pub error: Option<String>,
+}
+
+/// a file attached to the todo item, the content is kept in the [`BlobStore`]
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: i32,
+    pub item_id: i32,
+    pub name: String,
+    pub mime: String,
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: i32,
+    pub item_id: i32,
+    pub author: String,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    /// the body is changed after created
+    pub edited: bool,
+}
+
+/// one entry of the activity feed of an item, the comments and the changes on it
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Activity {
+    Comment(Comment),
+    PriorityChanged {
+        from: PriorityLevel,
+        to: PriorityLevel,
+        at: DateTime<Utc>,
+    },
+    DoneChanged {
+        done: bool,
+        at: DateTime<Utc>,
+    },
+}
+
+impl Activity {
+    /// when it happened
+    pub fn at(&self) -> DateTime<Utc> {
+        match self {
+            Self::Comment(comment) => comment.created_at,
+            Self::PriorityChanged { at, .. } | Self::DoneChanged { at, .. } => *at,
+        }
+    }
+}
+
+/// a span of time spent on the item, `stopped_at` is none while the timer is running
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeEntry {
+    pub id: i32,
+    pub item_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    /// the length of the span, up to now for the running timer
+    pub seconds: i64,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeTotal<K> {
+    pub key: K,
+    pub seconds: i64,
+}
+
+/// the time logged, the running timer counts up to now
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeTotals {
+    pub by_item: Vec<TimeTotal<i32>>,
+    /// an item with many tags counts for every tag
+    pub by_tag: Vec<TimeTotal<Tag>>,
+    /// a span across midnight is split into both days
+    pub by_day: Vec<TimeTotal<NaiveDate>>,
+}
+
+/// the estimates of a group of items
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EstimateSum {
+    pub items: i32,
+    /// how many items have an estimate
+    pub estimated: i32,
+    pub minutes: i64,
+    pub points: i64,
+}
+
+/// the estimates of the items in the group, split by the done state
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EstimateRollup<K> {
+    pub key: K,
+    pub open: EstimateSum,
+    pub done: EstimateSum,
+}
+
+/// the items created and completed in a period, and the items still open at its end
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeriodStats {
+    pub start: NaiveDate,
+    pub created: i32,
+    pub completed: i32,
+    pub open: i32,
+}
+
+/// the items of the group created in the range, and how many of them are done
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CompletionRate<K> {
+    pub key: K,
+    pub items: i32,
+    pub done: i32,
+    /// none if no item is created
+    pub rate: Option<f64>,
+}
+
+/// the progress in a range of days, only the items not deleted are counted
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    /// every day of the range
+    pub days: Vec<PeriodStats>,
+    /// the weeks start on monday, only the days in the range are counted
+    pub weeks: Vec<PeriodStats>,
+    pub by_priority: Vec<CompletionRate<PriorityLevel>>,
+    pub by_tag: Vec<CompletionRate<Tag>>,
+    /// of the items completed in the range, none if no such item
+    pub average_seconds_to_complete: Option<i64>,
+    /// the days in a row with any item completed, up to the last day of the range or the
+    /// day before it
+    pub current_streak: i32,
+    /// the most days in a row with any item completed in the range
+    pub longest_streak: i32,
+}
+
+/// a condition on the items, the saved searches keep it as json
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum SearchFilter {
+    /// every filter matches, an empty list matches every item
+    All {
+        filters: Vec<SearchFilter>,
+    },
+    /// any filter matches, an empty list matches no item
+    Any {
+        filters: Vec<SearchFilter>,
+    },
+    Not {
+        filter: Box<SearchFilter>,
+    },
+    Done {
+        done: bool,
+    },
+    /// the priority is any of the levels
+    Priority {
+        levels: Vec<PriorityLevel>,
+    },
+    /// the item is bind with the tag, the tag not exist matches no item
+    Tag {
+        tag: Tag,
+        /// the items bind with any descendant of the tag match too
+        #[serde(default)]
+        include_descendants: bool,
+    },
+    /// the message or the notes contain the text
+    Text {
+        text: String,
+    },
+    /// the item has the date, on or between the days if given. the days of the
+    /// timestamps are in UTC
+    Date {
+        field: DateField,
+        #[serde(default)]
+        from: Option<NaiveDate>,
+        #[serde(default)]
+        to: Option<NaiveDate>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DateField {
+    Due,
+    Created,
+    Completed,
+}
+
+/// a named filter the user comes back to, like a smart list
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub id: i32,
+    pub name: String,
+    pub filter: SearchFilter,
+}
+
+/// all the operates on the todo store, shared by the tauri commands, the http api and the cli
+#[derive(Debug, Clone)]
+pub struct TodoService {
+    pool: SqlitePool,
+    tag_policy: TagPolicy,
+    limits: InputLimits,
+    blob_store: BlobStore,
+}
+
+impl TodoService {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            tag_policy: TagPolicy::default(),
+            limits: InputLimits::default(),
+            blob_store: BlobStore::new(AttachmentConfig::default().dir_or(None)),
+        }
+    }
+
+    /// the policy the tag names are normalized with
+    pub fn with_tag_policy(mut self, tag_policy: TagPolicy) -> Self {
+        self.tag_policy = tag_policy;
+        self
+    }
+
+    /// the limits the input of the user checked with
+    pub fn with_limits(mut self, limits: InputLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// where the attached files are stored
+    pub fn with_blob_store(mut self, blob_store: BlobStore) -> Self {
+        self.blob_store = blob_store;
+        self
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+
+    pub fn tag_policy(&self) -> &TagPolicy {
+        &self.tag_policy
+    }
+
+    pub fn limits(&self) -> &InputLimits {
+        &self.limits
+    }
+
+    pub fn blob_store(&self) -> &BlobStore {
+        &self.blob_store
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_service {
+    use crate::database::models::test_sqlite::init;
+
+    use chrono::NaiveDate;
+
+    use super::{Estimate, PriorityLevel, TodoItem, TodoService};
+
+    /// every call get a new empty in memory database
+    pub async fn service() -> TodoService {
+        TodoService::new(init().await)
+    }
+
+    pub fn item(message: &str) -> ItemFixture {
+        ItemFixture(TodoItem {
+            message: message.into(),
+            priority: PriorityLevel::Medium,
+            done: false,
+            blocked: false,
+            estimate: None,
+            due: None,
+            tags: vec![],
+        })
+    }
+
+    /// the item to save or to expect, open in medium priority without tags by default
+    pub struct ItemFixture(TodoItem);
+
+    impl ItemFixture {
+        pub fn priority(mut self, priority: PriorityLevel) -> Self {
+            self.0.priority = priority;
+            self
+        }
+
+        pub fn done(mut self) -> Self {
+            self.0.done = true;
+            self
+        }
+
+        pub fn estimate(mut self, estimate: Estimate) -> Self {
+            self.0.estimate = Some(estimate);
+            self
+        }
+
+        pub fn due(mut self, due: NaiveDate) -> Self {
+            self.0.due = Some(due);
+            self
+        }
+
+        pub fn tag(mut self, tag: &str) -> Self {
+            self.0.tags.push(tag.into());
+            self
+        }
+
+        pub fn tags(self, tags: &[&str]) -> Self {
+            tags.iter().fold(self, |this, tag| this.tag(tag))
+        }
+
+        pub fn build(self) -> TodoItem {
+            self.0
+        }
+
+        /// save the item, return the id of it
+        pub async fn save(self, service: &TodoService) -> i32 {
+            service
+                .save_todo_item(self.0)
+                .await
+                .expect("save item fixture failure")
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use pulldown_cmark::{html, Options, Parser};
+
+use crate::database::models::todo_item::TodoItemEntity;
+
+use super::validate::Validator;
+use super::{OrNotFound, ServiceError, ServiceResult, TodoItem, TodoService};
+
+impl TodoService {
+    /// the markdown notes of the item, empty if never written
+    pub async fn fetch_notes(&self, item_id: i32) -> ServiceResult<String> {
+        let notes = TodoItemEntity::fetch_notes(self.pool(), item_id)
+            .await
+            .or_not_found(|| ServiceError::ItemNotFound(item_id))?;
+        Ok(notes.unwrap_or_default())
+    }
+
+    pub async fn edit_notes(&self, item_id: i32, notes: String) -> ServiceResult<()> {
+        Validator::new(self.limits())
+            .notes("notes", &notes)
+            .finish()?;
+        TodoItemEntity::update_notes(self.pool(), item_id, notes)
+            .await
+            .or_not_found(|| ServiceError::ItemNotFound(item_id))?;
+        Ok(())
+    }
+
+    /// the notes rendered to html, safe to show in the webview
+    pub async fn render_notes(&self, item_id: i32) -> ServiceResult<String> {
+        Ok(render_markdown(&self.fetch_notes(item_id).await?))
+    }
+
+    /// the items whose message or notes contain the keyword, in the manual order
+    pub async fn search_todo_item(&self, keyword: String) -> ServiceResult<Vec<(i32, TodoItem)>> {
+        let models = TodoItemEntity::search(self.pool(), &keyword).await?;
+        self.with_tags(models).await
+    }
+}
+
+/// the raw html, scripts and unsafe links in the markdown are removed
+fn render_markdown(markdown: &str) -> String {
+    let parser = Parser::new_ext(
+        markdown,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    );
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, parser);
+    ammonia::clean(&unsafe_html)
+}
+
+#[cfg(test)]
+mod test_notes {
+    use crate::service::test_service::{item, service};
+
+    use super::render_markdown;
+
+    #[test]
+    fn test_render_markdown() {
+        assert_eq!(
+            render_markdown("# Title\n\n**bold** ~~old~~"),
+            "<h1>Title</h1>\n<p><strong>bold</strong> <del>old</del></p>\n"
+        );
+        // the script and the javascript link are dropped
+        assert_eq!(
+            render_markdown("<script>alert(1)</script>\n\n[link](javascript:alert(1))"),
+            "\n<p><a rel=\"noopener noreferrer\">link</a></p>\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notes() {
+        let service = service().await;
+        let id = item("Foo").save(&service).await;
+        assert_eq!(service.fetch_notes(id).await.unwrap(), "");
+
+        service
+            .edit_notes(id, "*call* the 100% shop".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            service.fetch_notes(id).await.unwrap(),
+            "*call* the 100% shop"
+        );
+        assert_eq!(
+            service.render_notes(id).await.unwrap(),
+            "<p><em>call</em> the 100% shop</p>\n"
+        );
+
+        let missing = id + 1;
+        assert!(service
+            .fetch_notes(missing)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert!(service
+            .edit_notes(missing, "".into())
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let service = service().await;
+        let foo = item("Buy milk").save(&service).await;
+        let bar = item("Call Bob").save(&service).await;
+        let baz = item("Write 50% off").save(&service).await;
+        service
+            .edit_notes(bar, "ask about the MILK price".into())
+            .await
+            .unwrap();
+
+        let search = |keyword: &str| {
+            let keyword = keyword.to_owned();
+            let service = &service;
+            async move {
+                service
+                    .search_todo_item(keyword)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        // the message and the notes are both matched, ignoring case
+        assert_eq!(search("milk").await, [foo, bar]);
+        // the wildcards are matched literally
+        assert_eq!(search("%").await, [baz]);
+        assert_eq!(search("_").await, Vec::<i32>::new());
+        // the tags are returned with the items
+        assert_eq!(
+            service.search_todo_item("bob".into()).await.unwrap(),
+            [(bar, item("Call Bob").build())]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! turn a line typed by the user into a todo item, like
+//! `Fix login bug !high #backend #urgent tomorrow`
+//!
+//! - `!veryhigh`, `!high`, `!medium`, `!low`, `!verylow`, or `!1` to `!5`, set the priority,
+//!   the last one wins
+//! - `#tag` binds the tag
+//! - the first date found is the due date: `today`, `tomorrow`, a weekday like `friday`,
+//!   `next week`, `next friday`, `in 3 days`, `in 2 weeks`, `2022-10-21`, `oct 21` or `21 oct`
+//! - a word starts with `\` is kept in the message as it is, without the `\`
+//!
+//! the other words are the message
+
+use chrono::{Datelike, Duration, Month, NaiveDate, Weekday};
+
+use crate::database::models::todo_item::TodoItemEntity;
+
+use super::{PriorityLevel, ServiceError, ServiceResult, Tag, TodoItem, TodoService};
+
+/// what a quick add line is made of
+#[derive(Debug, PartialEq, Eq)]
+pub struct QuickAdd {
+    pub message: String,
+    pub priority: Option<PriorityLevel>,
+    pub tags: Vec<Tag>,
+    pub due: Option<NaiveDate>,
+}
+
+impl QuickAdd {
+    /// parse the line, the relative dates are counted from `today`
+    pub fn parse(line: &str, today: NaiveDate) -> Self {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let mut message = Vec::<&str>::new();
+        let mut priority = None;
+        let mut tags = Vec::<Tag>::new();
+        let mut due = None;
+
+        let mut idx = 0;
+        while idx < words.len() {
+            let word = words[idx];
+            idx += 1;
+            if let Some(word) = word.strip_prefix('\\').filter(|word| !word.is_empty()) {
+                message.push(word);
+            } else if let Some(level) = word.strip_prefix('!').and_then(priority_level) {
+                priority = Some(level);
+            } else if let Some(tag) = word.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+                if !tags.iter().any(|exist| exist == tag) {
+                    tags.push(tag.into());
+                }
+            } else if let Some((date, len)) =
+                parse_date(&words[idx - 1..], today).filter(|_| due.is_none())
+            {
+                due = Some(date);
+                idx += len - 1;
+            } else {
+                message.push(word);
+            }
+        }
+
+        Self {
+            message: message.join(" "),
+            priority,
+            tags,
+            due,
+        }
+    }
+
+    /// the item to save, in medium priority if not given
+    pub fn into_todo_item(self) -> TodoItem {
+        TodoItem {
+            message: self.message,
+            priority: self.priority.unwrap_or(PriorityLevel::Medium),
+            done: false,
+            blocked: false,
+            estimate: None,
+            due: self.due,
+            tags: self.tags,
+        }
+    }
+}
+
+impl TodoService {
+    /// parse the line into an item and save it, see [`QuickAdd`]. return the item as saved,
+    /// with the tags normalized
+    pub async fn quick_add(&self, line: &str, today: NaiveDate) -> ServiceResult<(i32, TodoItem)> {
+        let item = QuickAdd::parse(line, today).into_todo_item();
+        let id = self.save_todo_item(item).await?;
+        let models = TodoItemEntity::find_all_by_id(self.pool(), [id]).await?;
+        self.with_tags(models)
+            .await?
+            .pop()
+            .ok_or(ServiceError::ItemNotFound(id))
+    }
+}
+
+/// the priority after the `!`
+fn priority_level(marker: &str) -> Option<PriorityLevel> {
+    let level = match marker.to_lowercase().as_str() {
+        "veryhigh" | "very-high" | "1" => PriorityLevel::VeryHigh,
+        "high" | "2" => PriorityLevel::High,
+        "medium" | "3" => PriorityLevel::Medium,
+        "low" | "4" => PriorityLevel::Low,
+        "verylow" | "very-low" | "5" => PriorityLevel::VeryLow,
+        _ => return None,
+    };
+    Some(level)
+}
+
+/// the date at the start of the words and how many words it takes
+fn parse_date(words: &[&str], today: NaiveDate) -> Option<(NaiveDate, usize)> {
+    let words = words
+        .iter()
+        .take(3)
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>();
+    let word = |idx: usize| words.get(idx).map(String::as_str);
+
+    match (word(0)?, word(1), word(2)) {
+        ("today", ..) => Some((today, 1)),
+        ("tomorrow", ..) => Some((today + Duration::days(1), 1)),
+        ("next", Some("week"), _) => Some((next_weekday(today, Weekday::Mon), 2)),
+        ("next", Some(day), _) => weekday(day).map(|day| (next_weekday(today, day), 2)),
+        ("in", Some(count), Some(unit)) => {
+            let count = count.parse::<u16>().ok()?;
+            let days = match unit {
+                "day" | "days" => i64::from(count),
+                "week" | "weeks" => i64::from(count) * 7,
+                _ => return None,
+            };
+            Some((today + Duration::days(days), 3))
+        }
+        (first, second, _) => {
+            if let Some(day) = weekday(first) {
+                return Some((next_weekday(today, day), 1));
+            }
+            if let Ok(date) = NaiveDate::parse_from_str(first, "%Y-%m-%d") {
+                return Some((date, 1));
+            }
+            let (month, day) = match (month(first), second.and_then(day_of_month)) {
+                (Some(month), Some(day)) => (month, day),
+                _ => (month(second?)?, day_of_month(first)?),
+            };
+            let this_year = NaiveDate::from_ymd_opt(today.year(), month, day)?;
+            let date = if this_year < today {
+                NaiveDate::from_ymd_opt(today.year() + 1, month, day)?
+            } else {
+                this_year
+            };
+            Some((date, 2))
+        }
+    }
+}
+
+/// the weekday in full name, the short ones like `sat` are common words
+fn weekday(word: &str) -> Option<Weekday> {
+    let day = word.parse::<Weekday>().ok()?;
+    (word.len() > 3).then(|| day)
+}
+
+/// the month number, in full name or the first three letters
+fn month(word: &str) -> Option<u32> {
+    let month = word.parse::<Month>().ok()?;
+    Some(month.number_from_month())
+}
+
+/// the day of the month, with an ordinal suffix or not, like `21` and `21st`
+fn day_of_month(word: &str) -> Option<u32> {
+    let number = ["st", "nd", "rd", "th"]
+        .iter()
+        .find_map(|suffix| word.strip_suffix(suffix))
+        .unwrap_or(word);
+    number.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+/// the first `day` after `today`, a week later if today is that day
+fn next_weekday(today: NaiveDate, day: Weekday) -> NaiveDate {
+    let ahead = (7 + day.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    today + Duration::days(if ahead == 0 { 7 } else { ahead.into() })
+}
+
+#[cfg(test)]
+mod test_quick_add {
+    use chrono::NaiveDate;
+
+    use crate::service::{
+        test_service::{item, service},
+        PriorityLevel, ServiceError, ValidationError,
+    };
+
+    use super::QuickAdd;
+
+    /// the line, and the message, priority, tags and due date parsed from it
+    type Case = (
+        &'static str,
+        &'static str,
+        Option<PriorityLevel>,
+        &'static [&'static str],
+        Option<NaiveDate>,
+    );
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    #[test]
+    fn test_parse() {
+        use PriorityLevel::*;
+        // a wednesday
+        let today = date(2022, 10, 19);
+        let cases: &[Case] = &[
+            (
+                "Fix login bug !high #backend #urgent tomorrow",
+                "Fix login bug",
+                Some(High),
+                &["backend", "urgent"],
+                Some(date(2022, 10, 20)),
+            ),
+            ("Buy milk", "Buy milk", None, &[], None),
+            ("  Buy   milk  ", "Buy milk", None, &[], None),
+            ("", "", None, &[], None),
+            // priorities
+            ("!1 Deploy", "Deploy", Some(VeryHigh), &[], None),
+            ("Deploy !LOW", "Deploy", Some(Low), &[], None),
+            ("Deploy !very-low", "Deploy", Some(VeryLow), &[], None),
+            ("a !2 b !4", "a b", Some(Low), &[], None),
+            ("Hello! world", "Hello! world", None, &[], None),
+            ("! alone", "! alone", None, &[], None),
+            ("!urgent task", "!urgent task", None, &[], None),
+            // tags
+            ("#a #b #a x", "x", None, &["a", "b"], None),
+            ("# heading", "# heading", None, &[], None),
+            ("Report #Work/Q4", "Report", None, &["Work/Q4"], None),
+            // escapes
+            ("\\#1 in line \\!high", "#1 in line !high", None, &[], None),
+            ("\\tomorrow", "tomorrow", None, &[], None),
+            // relative dates
+            ("call today", "call", None, &[], Some(today)),
+            ("TOMORROW", "", None, &[], Some(date(2022, 10, 20))),
+            (
+                "friday review",
+                "review",
+                None,
+                &[],
+                Some(date(2022, 10, 21)),
+            ),
+            ("wednesday", "", None, &[], Some(date(2022, 10, 26))),
+            (
+                "Monday standup",
+                "standup",
+                None,
+                &[],
+                Some(date(2022, 10, 24)),
+            ),
+            ("sat down", "sat down", None, &[], None),
+            (
+                "next week plan",
+                "plan",
+                None,
+                &[],
+                Some(date(2022, 10, 24)),
+            ),
+            ("next friday", "", None, &[], Some(date(2022, 10, 21))),
+            ("next thing", "next thing", None, &[], None),
+            ("in 3 days", "", None, &[], Some(date(2022, 10, 22))),
+            ("in 1 week", "", None, &[], Some(date(2022, 10, 26))),
+            ("in 2 weeks", "", None, &[], Some(date(2022, 11, 2))),
+            ("in the morning", "in the morning", None, &[], None),
+            ("in 3 apples", "in 3 apples", None, &[], None),
+            ("log in 5", "log in 5", None, &[], None),
+            // absolute dates
+            (
+                "2022-12-01 release",
+                "release",
+                None,
+                &[],
+                Some(date(2022, 12, 1)),
+            ),
+            ("2022-13-01 release", "2022-13-01 release", None, &[], None),
+            ("oct 21 party", "party", None, &[], Some(date(2022, 10, 21))),
+            ("party 21 oct", "party", None, &[], Some(date(2022, 10, 21))),
+            ("oct 19", "", None, &[], Some(today)),
+            ("March 5th", "", None, &[], Some(date(2023, 3, 5))),
+            ("may 2nd", "", None, &[], Some(date(2023, 5, 2))),
+            ("december 31", "", None, &[], Some(date(2022, 12, 31))),
+            ("feb 30", "feb 30", None, &[], None),
+            ("may be", "may be", None, &[], None),
+            // only the first date is the due date
+            (
+                "tomorrow and today",
+                "and today",
+                None,
+                &[],
+                Some(date(2022, 10, 20)),
+            ),
+            (
+                "Submit report !3 TOMORROW #Work",
+                "Submit report",
+                Some(Medium),
+                &["Work"],
+                Some(date(2022, 10, 20)),
+            ),
+        ];
+
+        for &(line, message, priority, tags, due) in cases {
+            assert_eq!(
+                QuickAdd::parse(line, today),
+                QuickAdd {
+                    message: message.into(),
+                    priority,
+                    tags: tags.iter().map(|&tag| tag.into()).collect(),
+                    due,
+                },
+                "{line:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quick_add() {
+        let service = service().await;
+        let today = date(2022, 10, 19);
+        let (id, saved) = service
+            .quick_add("Fix login bug !high #backend tomorrow", today)
+            .await
+            .unwrap();
+        let expect = item("Fix login bug")
+            .priority(PriorityLevel::High)
+            .due(date(2022, 10, 20))
+            .tag("backend")
+            .build();
+        assert_eq!(saved, expect);
+        assert_eq!(service.fetch_all_todo_item().await.unwrap(), [(id, expect)]);
+
+        service.edit_due(id, None).await.unwrap();
+        assert_eq!(service.fetch_all_todo_item().await.unwrap()[0].1.due, None);
+
+        assert!(matches!(
+            service.quick_add("!high #backend", today).await,
+            Err(ServiceError::Validation(ValidationError(fields))) if fields[0].field == "message"
+        ));
+    }
+}
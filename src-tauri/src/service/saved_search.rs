@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use crate::database::models::{
+    saved_search::{SavedSearchEntity, SavedSearchModel},
+    tags::TagEntity,
+    todo_item::{ItemCond, TodoItem as ItemColumn, TodoItemEntity},
+};
+
+use super::{
+    validate::Validator, DateField, FieldError, OrNotFound, SavedSearch, SearchFilter,
+    ServiceError, ServiceResult, Tag, TodoItem, TodoService, ValidationError,
+};
+
+impl TodoService {
+    pub async fn create_saved_search(
+        &self,
+        name: String,
+        filter: SearchFilter,
+    ) -> ServiceResult<SavedSearch> {
+        self.check_saved_search(None, &name, &filter).await?;
+        let id = SavedSearchEntity::save(self.pool(), name.clone(), to_json(&filter)).await?;
+        Ok(SavedSearch { id, name, filter })
+    }
+
+    /// replace the name and the filter of the saved search
+    pub async fn edit_saved_search(
+        &self,
+        search_id: i32,
+        name: String,
+        filter: SearchFilter,
+    ) -> ServiceResult<SavedSearch> {
+        self.check_saved_search(Some(search_id), &name, &filter)
+            .await?;
+        let model = SavedSearchModel {
+            id: search_id,
+            name: name.clone(),
+            filter: to_json(&filter),
+        };
+        SavedSearchEntity::update(self.pool(), model)
+            .await
+            .or_not_found(|| ServiceError::SavedSearchNotFound(search_id))?;
+        Ok(SavedSearch {
+            id: search_id,
+            name,
+            filter,
+        })
+    }
+
+    pub async fn delete_saved_search(&self, search_id: i32) -> ServiceResult<()> {
+        SavedSearchEntity::remove(self.pool(), search_id)
+            .await
+            .or_not_found(|| ServiceError::SavedSearchNotFound(search_id))
+    }
+
+    /// all the saved searches in the order of the name
+    pub async fn fetch_saved_searches(&self) -> ServiceResult<Vec<SavedSearch>> {
+        SavedSearchEntity::fetch_all(self.pool())
+            .await?
+            .into_iter()
+            .map(to_saved_search)
+            .collect()
+    }
+
+    /// the items match the filter of the saved search now, in the manual order
+    pub async fn evaluate_saved_search(
+        &self,
+        search_id: i32,
+    ) -> ServiceResult<Vec<(i32, TodoItem)>> {
+        let pool = self.pool();
+        let SavedSearch { filter, .. } = SavedSearchEntity::find_by_id(pool, search_id)
+            .await
+            .or_not_found(|| ServiceError::SavedSearchNotFound(search_id))
+            .and_then(to_saved_search)?;
+
+        // the tags are looked up before, so the filter can be turned into the condition at once
+        let mut tag_ids = HashMap::new();
+        for (tag, include_descendants) in filter_tags(&filter) {
+            let ids = match TagEntity::get_id(pool, self.tag_policy(), &tag).await {
+                Ok(id) if include_descendants => {
+                    let mut ids = TagEntity::fetch_descendants(pool, id).await?;
+                    ids.push(id);
+                    ids
+                }
+                Ok(id) => vec![id],
+                Err(sqlx::Error::RowNotFound) => vec![],
+                Err(err) => return Err(err.into()),
+            };
+            tag_ids.insert((tag, include_descendants), ids);
+        }
+
+        let cond = to_item_cond(filter, &tag_ids);
+        let models = TodoItemEntity::fetch_all_by_cond(pool, cond).await?;
+        self.with_tags(models).await
+    }
+
+    /// the name is not used by another saved search, and the tags in the filter are valid
+    async fn check_saved_search(
+        &self,
+        search_id: Option<i32>,
+        name: &str,
+        filter: &SearchFilter,
+    ) -> ServiceResult<()> {
+        let tags = filter_tags(filter)
+            .into_iter()
+            .map(|(tag, _)| tag)
+            .collect::<Vec<_>>();
+        Validator::new(self.limits())
+            .message("name", name)
+            .tags("filter.tags", &tags)
+            .finish()?;
+
+        match SavedSearchEntity::find_by_name(self.pool(), name).await? {
+            Some(other) if Some(other.id) != search_id => Err(ValidationError(vec![FieldError {
+                field: "name".into(),
+                message: "is used by another saved search".into(),
+            }])
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn to_json(filter: &SearchFilter) -> String {
+    serde_json::to_string(filter).expect("Unreachable")
+}
+
+/// the filters are always written by [`to_json`], so a bad one is reported as the data in
+/// the database is broken
+fn to_saved_search(
+    SavedSearchModel { id, name, filter }: SavedSearchModel,
+) -> ServiceResult<SavedSearch> {
+    let filter = serde_json::from_str(&filter).map_err(|err| sqlx::Error::Decode(err.into()))?;
+    Ok(SavedSearch { id, name, filter })
+}
+
+/// the tags in the filter, with whether their descendants are included
+fn filter_tags(filter: &SearchFilter) -> Vec<(Tag, bool)> {
+    match filter {
+        SearchFilter::All { filters } | SearchFilter::Any { filters } => {
+            filters.iter().flat_map(filter_tags).collect()
+        }
+        SearchFilter::Not { filter } => filter_tags(filter),
+        SearchFilter::Tag {
+            tag,
+            include_descendants,
+        } => vec![(tag.clone(), *include_descendants)],
+        SearchFilter::Done { .. }
+        | SearchFilter::Priority { .. }
+        | SearchFilter::Text { .. }
+        | SearchFilter::Date { .. } => vec![],
+    }
+}
+
+/// turn the filter into the condition of the entity, the tags are looked up in `tag_ids`
+fn to_item_cond(filter: SearchFilter, tag_ids: &HashMap<(Tag, bool), Vec<i32>>) -> ItemCond {
+    let to_item_conds = |filters: Vec<SearchFilter>| {
+        filters
+            .into_iter()
+            .map(|filter| to_item_cond(filter, tag_ids))
+            .collect()
+    };
+    match filter {
+        SearchFilter::All { filters } => ItemCond::All(to_item_conds(filters)),
+        SearchFilter::Any { filters } => ItemCond::Any(to_item_conds(filters)),
+        SearchFilter::Not { filter } => ItemCond::Not(Box::new(to_item_cond(*filter, tag_ids))),
+        SearchFilter::Done { done } => ItemCond::Done(done),
+        SearchFilter::Priority { levels } => {
+            ItemCond::Priority(levels.into_iter().map(Into::into).collect())
+        }
+        SearchFilter::Tag {
+            tag,
+            include_descendants,
+        } => ItemCond::Tags(
+            tag_ids
+                .get(&(tag, include_descendants))
+                .cloned()
+                .unwrap_or_default(),
+        ),
+        SearchFilter::Text { text } => ItemCond::Text(text),
+        SearchFilter::Date { field, from, to } => ItemCond::Date {
+            col: match field {
+                DateField::Due => ItemColumn::DueOn,
+                DateField::Created => ItemColumn::CreatedAt,
+                DateField::Completed => ItemColumn::CompletedAt,
+            },
+            from,
+            to,
+        },
+    }
+}
+
+#[cfg(test)]
+mod test_saved_search {
+    use crate::service::{
+        test_service::{item, service},
+        DateField, PriorityLevel, SearchFilter, ServiceError, TodoItem, ValidationError,
+    };
+
+    #[tokio::test]
+    async fn test_saved_search() {
+        let service = service().await;
+        let login = item("Fix login")
+            .priority(PriorityLevel::High)
+            .tags(&["backend", "backend/auth"])
+            .save(&service)
+            .await;
+        let deploy = item("Deploy")
+            .priority(PriorityLevel::VeryHigh)
+            .tag("backend")
+            .save(&service)
+            .await;
+        let style = item("Fix style")
+            .priority(PriorityLevel::High)
+            .tag("frontend")
+            .save(&service)
+            .await;
+        service.set_done(deploy, true).await.unwrap();
+
+        let filter = SearchFilter::All {
+            filters: vec![
+                SearchFilter::Done { done: false },
+                SearchFilter::Priority {
+                    levels: vec![PriorityLevel::VeryHigh, PriorityLevel::High],
+                },
+                SearchFilter::Tag {
+                    tag: "backend".into(),
+                    include_descendants: false,
+                },
+            ],
+        };
+        let search = service
+            .create_saved_search("Backend".into(), filter.clone())
+            .await
+            .unwrap();
+        let ids =
+            |items: Vec<(i32, TodoItem)>| items.into_iter().map(|(id, _)| id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(service.evaluate_saved_search(search.id).await.unwrap()),
+            [login]
+        );
+
+        let filter = SearchFilter::Any {
+            filters: vec![
+                SearchFilter::Text {
+                    text: "STYLE".into(),
+                },
+                SearchFilter::Not {
+                    filter: Box::new(SearchFilter::Date {
+                        field: DateField::Completed,
+                        from: None,
+                        to: None,
+                    }),
+                },
+            ],
+        };
+        let edited = service
+            .edit_saved_search(search.id, "Open".into(), filter.clone())
+            .await
+            .unwrap();
+        assert_eq!(service.fetch_saved_searches().await.unwrap(), [edited]);
+        assert_eq!(
+            ids(service.evaluate_saved_search(search.id).await.unwrap()),
+            [login, style]
+        );
+
+        // the items without notes do not contain the text either
+        service
+            .edit_notes(login, "Login with SSO".into())
+            .await
+            .unwrap();
+        let no_sso = SearchFilter::Not {
+            filter: Box::new(SearchFilter::Text { text: "sso".into() }),
+        };
+        let no_sso = service
+            .create_saved_search("No SSO".into(), no_sso)
+            .await
+            .unwrap();
+        assert_eq!(
+            ids(service.evaluate_saved_search(no_sso.id).await.unwrap()),
+            [deploy, style]
+        );
+
+        // the tags not exist match nothing
+        let missing = SearchFilter::Tag {
+            tag: "missing".into(),
+            include_descendants: true,
+        };
+        let missing = service
+            .create_saved_search("Missing".into(), missing)
+            .await
+            .unwrap();
+        assert!(service
+            .evaluate_saved_search(missing.id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(matches!(
+            service.create_saved_search("Open".into(), filter).await,
+            Err(ServiceError::Validation(ValidationError(fields))) if fields[0].field == "name"
+        ));
+        service.delete_saved_search(search.id).await.unwrap();
+        assert!(service
+            .evaluate_saved_search(search.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::database::models::{
+    tag_item_bind::{BindDoneCount, BindEntity},
+    tags::{TagEntity, TagModel},
+    todo_item::{DayCount, DoneCount, TodoItem, TodoItemEntity},
+};
+
+use super::{
+    validate::Validator, CompletionRate, PeriodStats, PriorityLevel, ServiceResult, Stats,
+    TodoService,
+};
+
+impl TodoService {
+    /// the stats of the days from `from` to `to`, both included. the days are in the time
+    /// zone `utc_offset` minutes east of UTC
+    pub async fn fetch_stats(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        utc_offset: i32,
+    ) -> ServiceResult<Stats> {
+        Validator::new(self.limits())
+            .date_range("to", from, to)
+            .utc_offset("utcOffset", utc_offset)
+            .finish()?;
+        let pool = self.pool();
+
+        let created = by_day(
+            TodoItemEntity::count_by_day(pool, TodoItem::CreatedAt, utc_offset, from, to).await?,
+        );
+        let completed = by_day(
+            TodoItemEntity::count_by_day(pool, TodoItem::CompletedAt, utc_offset, from, to).await?,
+        );
+        let mut open = TodoItemEntity::count_open_before(pool, utc_offset, from).await?;
+        let mut days = vec![];
+        for day in from.iter_days().take_while(|day| *day <= to) {
+            let created = created.get(&day).copied().unwrap_or_default();
+            let completed = completed.get(&day).copied().unwrap_or_default();
+            open += created - completed;
+            days.push(PeriodStats {
+                start: day,
+                created,
+                completed,
+                open,
+            });
+        }
+
+        let mut weeks = Vec::<PeriodStats>::new();
+        for day in &days {
+            let monday =
+                day.start - Duration::days(day.start.weekday().num_days_from_monday().into());
+            match weeks.last_mut() {
+                Some(week) if week.start == monday => {
+                    week.created += day.created;
+                    week.completed += day.completed;
+                    week.open = day.open;
+                }
+                _ => weeks.push(PeriodStats {
+                    start: monday,
+                    created: day.created,
+                    completed: day.completed,
+                    open: day.open,
+                }),
+            }
+        }
+
+        let counts = TodoItemEntity::count_done_by_priority(pool, utc_offset, from, to).await?;
+        let by_priority = [
+            PriorityLevel::VeryHigh,
+            PriorityLevel::High,
+            PriorityLevel::Medium,
+            PriorityLevel::Low,
+            PriorityLevel::VeryLow,
+        ]
+        .into_iter()
+        .map(|level| {
+            let count = counts
+                .iter()
+                .find(|count| PriorityLevel::from(count.priority) == level);
+            let (items, done) =
+                count.map_or((0, 0), |&DoneCount { items, done, .. }| (items, done));
+            rate(level, items, done)
+        })
+        .collect();
+
+        let counts = BindEntity::count_done_by_tag(pool, utc_offset, from, to)
+            .await?
+            .into_iter()
+            .map(|count: BindDoneCount| (count.tag_id, (count.items, count.done)))
+            .collect::<HashMap<_, _>>();
+        let by_tag = TagEntity::fetch_all(pool, None)
+            .await?
+            .into_iter()
+            .map(|TagModel { id, value, .. }| {
+                let (items, done) = counts.get(&id).copied().unwrap_or_default();
+                rate(value, items, done)
+            })
+            .collect();
+
+        let average_seconds_to_complete =
+            TodoItemEntity::average_seconds_to_complete(pool, utc_offset, from, to)
+                .await?
+                .map(|seconds| seconds.round() as i64);
+        let completion_days = TodoItemEntity::fetch_completion_days(pool, utc_offset, to).await?;
+        let (current_streak, longest_streak) = streaks(&completion_days, from, to);
+
+        Ok(Stats {
+            days,
+            weeks,
+            by_priority,
+            by_tag,
+            average_seconds_to_complete,
+            current_streak,
+            longest_streak,
+        })
+    }
+}
+
+fn by_day(counts: Vec<DayCount>) -> HashMap<NaiveDate, i32> {
+    counts
+        .into_iter()
+        .map(|DayCount { day, count }| (day, count))
+        .collect()
+}
+
+fn rate<K>(key: K, items: i32, done: i32) -> CompletionRate<K> {
+    CompletionRate {
+        key,
+        items,
+        done,
+        rate: (items > 0).then(|| f64::from(done) / f64::from(items)),
+    }
+}
+
+/// the current and the longest streak of the sorted days, the longest one only counts
+/// the days from `from`
+fn streaks(days: &[NaiveDate], from: NaiveDate, to: NaiveDate) -> (i32, i32) {
+    // the first and the last day of every run of days in a row
+    let mut runs = Vec::<(NaiveDate, NaiveDate)>::new();
+    for &day in days {
+        match runs.last_mut() {
+            Some((_, last)) if *last + Duration::days(1) == day => *last = day,
+            _ => runs.push((day, day)),
+        }
+    }
+    let length = |first: NaiveDate, last: NaiveDate| ((last - first).num_days() + 1).max(0) as i32;
+
+    let current = runs
+        .last()
+        .filter(|(_, last)| *last + Duration::days(1) >= to)
+        .map_or(0, |&(first, last)| length(first, last));
+    let longest = runs
+        .iter()
+        .map(|&(first, last)| length(first.max(from), last))
+        .max()
+        .unwrap_or(0);
+    (current, longest)
+}
+
+#[cfg(test)]
+mod test_stats {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use sqlx::query;
+
+    use crate::service::{
+        test_service::{item, service},
+        PeriodStats, PriorityLevel, ServiceError, TodoService,
+    };
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2022, 10, day)
+    }
+
+    fn period(start: u32, created: i32, completed: i32, open: i32) -> PeriodStats {
+        PeriodStats {
+            start: day(start),
+            created,
+            completed,
+            open,
+        }
+    }
+
+    /// save the item created and completed at the hours of the days in UTC
+    async fn save(
+        service: &TodoService,
+        priority: PriorityLevel,
+        tags: &[&str],
+        created: (u32, u32),
+        completed: Option<(u32, u32)>,
+    ) -> i32 {
+        let mut fixture = item("Foo").priority(priority).tags(tags);
+        if completed.is_some() {
+            fixture = fixture.done();
+        }
+        let id = fixture.save(service).await;
+        let at = |(day, hour)| Utc.ymd(2022, 10, day).and_hms(hour, 0, 0);
+        query("UPDATE todo_item SET created_at = ?, completed_at = ? WHERE id = ?")
+            .bind(at(created))
+            .bind(completed.map(at))
+            .bind(id)
+            .execute(service.pool())
+            .await
+            .unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn test_stats() {
+        let service = service().await;
+        save(
+            &service,
+            PriorityLevel::High,
+            &["work"],
+            (3, 9),
+            Some((4, 9)),
+        )
+        .await;
+        // completed on 6th in UTC+2
+        save(
+            &service,
+            PriorityLevel::High,
+            &["work"],
+            (4, 10),
+            Some((5, 23)),
+        )
+        .await;
+        save(&service, PriorityLevel::Medium, &[], (5, 8), Some((5, 9))).await;
+        save(&service, PriorityLevel::Low, &["home"], (1, 8), None).await;
+        // done in an older version, not counted anywhere
+        let old = save(&service, PriorityLevel::Low, &[], (1, 8), None).await;
+        query("UPDATE todo_item SET done = TRUE, created_at = NULL WHERE id = ?")
+            .bind(old)
+            .execute(service.pool())
+            .await
+            .unwrap();
+
+        let stats = service.fetch_stats(day(3), day(10), 120).await.unwrap();
+        assert_eq!(
+            stats.days,
+            [
+                period(3, 1, 0, 2),
+                period(4, 1, 1, 2),
+                period(5, 1, 1, 2),
+                period(6, 0, 1, 1),
+                period(7, 0, 0, 1),
+                period(8, 0, 0, 1),
+                period(9, 0, 0, 1),
+                period(10, 0, 0, 1),
+            ]
+        );
+        assert_eq!(stats.weeks, [period(3, 3, 3, 1), period(10, 0, 0, 1)]);
+
+        let rates = stats
+            .by_priority
+            .iter()
+            .map(|rate| (rate.key, rate.items, rate.rate))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rates,
+            [
+                (PriorityLevel::VeryHigh, 0, None),
+                (PriorityLevel::High, 2, Some(1.0)),
+                (PriorityLevel::Medium, 1, Some(1.0)),
+                (PriorityLevel::Low, 0, None),
+                (PriorityLevel::VeryLow, 0, None),
+            ]
+        );
+        let tags = stats
+            .by_tag
+            .iter()
+            .map(|rate| (rate.key.as_str(), rate.done))
+            .collect::<Vec<_>>();
+        assert_eq!(tags, [("work", 2), ("home", 0)]);
+
+        // 24, 37 and 1 hours
+        assert_eq!(stats.average_seconds_to_complete, Some(74400));
+        assert_eq!((stats.current_streak, stats.longest_streak), (0, 3));
+        let stats = service.fetch_stats(day(5), day(7), 120).await.unwrap();
+        assert_eq!((stats.current_streak, stats.longest_streak), (3, 2));
+
+        assert!(matches!(
+            service.fetch_stats(day(5), day(4), 24 * 60).await,
+            Err(ServiceError::Validation(error)) if error.0.len() == 2
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_completed_at() {
+        let service = service().await;
+        let id = save(&service, PriorityLevel::Medium, &[], (3, 9), None).await;
+        let today = Utc::now().date().naive_utc();
+        let completed = |service: &TodoService| {
+            let service = service.clone();
+            async move {
+                let stats = service.fetch_stats(today, today, 0).await.unwrap();
+                stats.days[0].completed
+            }
+        };
+
+        service.set_done(id, true).await.unwrap();
+        assert_eq!(completed(&service).await, 1);
+        service.revert_done(id).await.unwrap();
+        assert_eq!(completed(&service).await, 0);
+        service.revert_done(id).await.unwrap();
+        assert_eq!(completed(&service).await, 1);
+    }
+}
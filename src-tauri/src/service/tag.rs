@@ -0,0 +1,493 @@
+use crate::database::models::tag_item_bind::BindCount;
+use crate::database::models::tag_item_bind::BindEntity;
+use crate::database::models::tags::TagCollision;
+use crate::database::models::tags::TagEntity;
+use crate::database::models::tags::TagModel;
+use crate::database::models::tags::TagNameError;
+use crate::database::models::todo_item::TodoItemEntity;
+
+use super::validate::Validator;
+use super::{
+    FieldError, OrNotFound, ServiceError, ServiceResult, Tag, TagMeta, TagNode, TagStats, TodoItem,
+    TodoService, ValidationError,
+};
+
+impl TodoService {
+    /// all tags in their manual order
+    pub async fn fetch_all_tags(&self) -> ServiceResult<Vec<(i32, Tag, TagMeta)>> {
+        // get all tags,group with tag id, value and meta
+        Ok(TagEntity::fetch_all(self.pool(), None)
+            .await?
+            .into_iter()
+            .map(
+                |TagModel {
+                     id,
+                     value,
+                     color,
+                     icon,
+                     description,
+                     position,
+                     ..
+                 }| {
+                    (
+                        id,
+                        value,
+                        TagMeta {
+                            color,
+                            icon,
+                            description,
+                            position,
+                        },
+                    )
+                },
+            )
+            .collect())
+    }
+
+    /// set the color, icon and description of the tag, `None` clear the field
+    pub async fn update_tag_meta(
+        &self,
+        tag_id: i32,
+        TagMeta {
+            color,
+            icon,
+            description,
+            ..
+        }: TagMeta,
+    ) -> ServiceResult<()> {
+        Validator::new(self.limits())
+            .color("color", color.as_deref())
+            .finish()?;
+        TagEntity::update_meta(self.pool(), tag_id, color, icon, description)
+            .await
+            .or_not_found(|| ServiceError::TagNotFound(tag_id))?;
+        Ok(())
+    }
+
+    /// sort the tags follow the order of `tag_ids`
+    pub async fn reorder_tags(&self, tag_ids: Vec<i32>) -> ServiceResult<()> {
+        TagEntity::reorder(self.pool(), tag_ids).await?;
+        Ok(())
+    }
+
+    /// the number of open and done items of every tag, in the order of the tags
+    pub async fn fetch_tag_stats(&self) -> ServiceResult<Vec<TagStats>> {
+        let pool = self.pool();
+        let mut stats = TagEntity::fetch_all(pool, None)
+            .await?
+            .into_iter()
+            .map(|TagModel { id, value, .. }| TagStats {
+                id,
+                value,
+                open: 0,
+                done: 0,
+            })
+            .collect::<Vec<_>>();
+
+        for BindCount {
+            tag_id,
+            done,
+            count,
+        } in BindEntity::count_by_done(pool).await?
+        {
+            if let Some(stat) = stats.iter_mut().find(|stat| stat.id == tag_id) {
+                if done {
+                    stat.done = count;
+                } else {
+                    stat.open = count;
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// remove the tags no item use, with `dry_run` only list them
+    pub async fn prune_unused_tags(&self, dry_run: bool) -> ServiceResult<Vec<(i32, Tag)>> {
+        Ok(TagEntity::prune_unused(self.pool(), dry_run)
+            .await?
+            .into_iter()
+            .map(|TagModel { id, value, .. }| (id, value))
+            .collect())
+    }
+
+    /// at most `limit` tags match the input, the often and recently used tags come first
+    pub async fn suggest_tags(&self, prefix: String, limit: u64) -> ServiceResult<Vec<(i32, Tag)>> {
+        Ok(
+            TagEntity::suggest(self.pool(), self.tag_policy(), &prefix, limit)
+                .await?
+                .into_iter()
+                .map(|TagModel { id, value, .. }| (id, value))
+                .collect(),
+        )
+    }
+
+    /// all tags with their parent, the frontend build the tree from it
+    pub async fn fetch_tag_tree(&self) -> ServiceResult<Vec<TagNode>> {
+        Ok(TagEntity::fetch_all(self.pool(), None)
+            .await?
+            .into_iter()
+            .map(
+                |TagModel {
+                     id,
+                     value,
+                     parent_id,
+                     ..
+                 }| TagNode {
+                    id,
+                    value,
+                    parent_id,
+                },
+            )
+            .collect())
+    }
+
+    /// move the tag under another tag, `None` move it to the top level
+    pub async fn move_tag(&self, tag_id: i32, parent_id: Option<i32>) -> ServiceResult<()> {
+        TagEntity::set_parent(self.pool(), tag_id, parent_id).await?;
+        Ok(())
+    }
+
+    /// the todo items bind with the tag, with `include_descendants`
+    /// the items bind with any descendant of the tag are included too
+    pub async fn fetch_all_tag_todo_item(
+        &self,
+        tag_id: i32,
+        include_descendants: bool,
+    ) -> ServiceResult<Vec<(i32, TodoItem)>> {
+        let pool = self.pool();
+        let mut tag_ids = vec![tag_id];
+        if include_descendants {
+            tag_ids.extend(TagEntity::fetch_descendants(pool, tag_id).await?);
+        }
+        let mut item_ids = Vec::new();
+        for tag_id in tag_ids {
+            item_ids.extend(BindEntity::fetch_all_item_id(pool, tag_id).await?);
+        }
+
+        self.with_tags(TodoItemEntity::find_all_by_id(pool, item_ids).await?)
+            .await
+    }
+
+    /// merge the duplicate tags into the target, the items bind with any source
+    /// are bind with the target after merge, then the sources are removed
+    pub async fn merge_tags(&self, source_ids: Vec<i32>, target_id: i32) -> ServiceResult<()> {
+        if source_ids.contains(&target_id) {
+            return Err(ValidationError(vec![FieldError {
+                field: "sourceIds".into(),
+                message: "must not contain the target tag".into(),
+            }])
+            .into());
+        }
+        TagEntity::merge(self.pool(), source_ids, target_id).await?;
+        Ok(())
+    }
+
+    /// update the normalized names of the tags after the policy changed,
+    /// return the tags become duplicated under the policy
+    pub async fn normalize_tags(&self) -> ServiceResult<Vec<TagCollision>> {
+        Ok(TagEntity::normalize(self.pool(), self.tag_policy()).await?)
+    }
+
+    pub async fn rename_tag(&self, tag_id: i32, tag_name: Tag) -> ServiceResult<()> {
+        Validator::new(self.limits())
+            .tag("tagName", &tag_name)
+            .finish()?;
+        match TagEntity::edit(self.pool(), self.tag_policy(), tag_id, &tag_name).await {
+            Err(TagNameError::NotFound(tag_id)) => Err(ServiceError::TagNotFound(tag_id)),
+            result => Ok(result?),
+        }
+    }
+
+    pub async fn create_tag(&self, tag_name: Tag) -> ServiceResult<i32> {
+        Validator::new(self.limits())
+            .tag("tagName", &tag_name)
+            .finish()?;
+        Ok(TagEntity::save(self.pool(), self.tag_policy(), &tag_name).await?)
+    }
+
+    pub async fn get_tag_id(&self, tag_name: Tag) -> ServiceResult<i32> {
+        TagEntity::get_id(self.pool(), self.tag_policy(), &tag_name)
+            .await
+            .or_not_found(|| ServiceError::TagNameNotFound(tag_name))
+    }
+
+    pub async fn delete_tag(&self, tag_name: Tag) -> ServiceResult<()> {
+        let pool = self.pool();
+        let tag_id = self.get_tag_id(tag_name.clone()).await?;
+        let parent_id = TagEntity::find_all_by_id(pool, [tag_id])
+            .await?
+            .pop()
+            .and_then(|tag| tag.parent_id);
+
+        // the binds, the children and the tag itself change together or not at all
+        let mut tx = pool.begin().await?;
+        BindEntity::remove_bind_tag_id(&mut tx, tag_id).await?;
+        // children of the tag take its place in the tree
+        TagEntity::move_children(&mut tx, tag_id, parent_id).await?;
+        // remove this
+        TagEntity::remove(&mut tx, self.tag_policy(), &tag_name).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test_tag {
+    use crate::service::{
+        test_service::{item, service},
+        EditMode, ServiceError, TagMeta, TagNode, TagStats, ValidationError,
+    };
+
+    #[tokio::test]
+    async fn test_tag_todo_item() {
+        let service = service().await;
+        let tag_id = service.create_tag("a".into()).await.unwrap();
+        let id = item("Foo").tags(&["a", "b"]).save(&service).await;
+        item("Bar").tag("b").save(&service).await;
+
+        let items = service
+            .fetch_all_tag_todo_item(tag_id, false)
+            .await
+            .unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].0, id);
+        assert_eq!(items[0].1.tags, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_rename_and_delete() {
+        let service = service().await;
+        let tag_id = service.create_tag("a".into()).await.unwrap();
+        let id = item("Foo").save(&service).await;
+        service
+            .edit_tag(id, EditMode::Add, "a".into())
+            .await
+            .unwrap();
+
+        service.rename_tag(tag_id, "b".into()).await.unwrap();
+        assert_eq!(service.get_tag_id("b".into()).await.unwrap(), tag_id);
+
+        service.delete_tag("b".into()).await.unwrap();
+        assert!(service.fetch_all_tags().await.unwrap().is_empty());
+        assert!(service
+            .fetch_all_tag_todo_item(tag_id, false)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            service
+                .get_tag_id("b".into())
+                .await
+                .unwrap_err()
+                .to_string(),
+            "Tag `b` not found"
+        );
+        assert!(service
+            .delete_tag("b".into())
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert!(service
+            .rename_tag(tag_id, "c".into())
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_tag_tree() {
+        let service = service().await;
+        let work = service.create_tag("work".into()).await.unwrap();
+        let backend = service.create_tag("backend".into()).await.unwrap();
+        let api = service.create_tag("api".into()).await.unwrap();
+        service.move_tag(backend, Some(work)).await.unwrap();
+        service.move_tag(api, Some(backend)).await.unwrap();
+
+        // move a tag under itself or its descendant make a cycle
+        assert!(service.move_tag(work, Some(work)).await.is_err());
+        assert!(service.move_tag(work, Some(api)).await.is_err());
+        assert!(matches!(
+            service.move_tag(api + 1, None).await,
+            Err(ServiceError::TagNotFound(missing)) if missing == api + 1
+        ));
+        assert!(matches!(
+            service.move_tag(work, Some(api + 1)).await,
+            Err(ServiceError::TagNotFound(missing)) if missing == api + 1
+        ));
+
+        let id = item("Foo").tag("api").save(&service).await;
+        assert!(service
+            .fetch_all_tag_todo_item(work, false)
+            .await
+            .unwrap()
+            .is_empty());
+        let items = service.fetch_all_tag_todo_item(work, true).await.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].0, id);
+
+        // children of a removed tag move to its parent
+        service.delete_tag("backend".into()).await.unwrap();
+        let tree = service.fetch_tag_tree().await.unwrap();
+        assert_eq!(
+            tree,
+            vec![
+                TagNode {
+                    id: work,
+                    value: "work".into(),
+                    parent_id: None
+                },
+                TagNode {
+                    id: api,
+                    value: "api".into(),
+                    parent_id: Some(work)
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_tag_meta() {
+        let service = service().await;
+        let a = service.create_tag("a".into()).await.unwrap();
+        let b = service.create_tag("b".into()).await.unwrap();
+
+        service
+            .update_tag_meta(
+                a,
+                TagMeta {
+                    color: Some("#ff8800".into()),
+                    icon: Some("🔥".into()),
+                    description: Some("urgent".into()),
+                    ..TagMeta::default()
+                },
+            )
+            .await
+            .unwrap();
+        // a color other than `#rrggbb` is rejected and nothing is saved
+        let orange = TagMeta {
+            color: Some("orange".into()),
+            ..TagMeta::default()
+        };
+        assert!(matches!(
+            service.update_tag_meta(b, orange).await,
+            Err(ServiceError::Validation(ValidationError(fields))) if fields[0].field == "color"
+        ));
+        service.reorder_tags(vec![b, a]).await.unwrap();
+        assert!(matches!(
+            service.reorder_tags(vec![a, b + 1, b]).await,
+            Err(ServiceError::TagNotFound(missing)) if missing == b + 1
+        ));
+
+        let tags = service.fetch_all_tags().await.unwrap();
+        assert_eq!(
+            tags,
+            vec![
+                (
+                    b,
+                    "b".to_string(),
+                    TagMeta {
+                        position: 0,
+                        ..TagMeta::default()
+                    }
+                ),
+                (
+                    a,
+                    "a".to_string(),
+                    TagMeta {
+                        color: Some("#ff8800".into()),
+                        icon: Some("🔥".into()),
+                        description: Some("urgent".into()),
+                        position: 1,
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_tags() {
+        let service = service().await;
+        let foo = item("Foo").tags(&["bug", "bugs"]).save(&service).await;
+        let bar = item("Bar").tag("defect").save(&service).await;
+        let bug = service.get_tag_id("bug".into()).await.unwrap();
+        let bugs = service.get_tag_id("bugs".into()).await.unwrap();
+        let defect = service.get_tag_id("defect".into()).await.unwrap();
+
+        service.merge_tags(vec![bugs, defect], bug).await.unwrap();
+
+        let tags = service.fetch_all_tags().await.unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].0, bug);
+        assert_eq!(
+            service.fetch_all_todo_item().await.unwrap(),
+            vec![
+                (foo, item("Foo").tag("bug").build()),
+                (bar, item("Bar").tag("bug").build())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_merge_tags_not_found() {
+        let service = service().await;
+        let bug = service.create_tag("bug".into()).await.unwrap();
+        let bugs = service.create_tag("bugs".into()).await.unwrap();
+        let id = item("Foo").tag("bugs").save(&service).await;
+
+        // a missing target or source changes nothing
+        assert!(matches!(
+            service.merge_tags(vec![bugs], bugs + 100).await,
+            Err(ServiceError::TagNotFound(missing)) if missing == bugs + 100
+        ));
+        assert!(matches!(
+            service.merge_tags(vec![bugs, bugs + 100], bug).await,
+            Err(ServiceError::TagNotFound(missing)) if missing == bugs + 100
+        ));
+        assert_eq!(service.fetch_all_tags().await.unwrap().len(), 2);
+        assert_eq!(
+            service.fetch_all_todo_item().await.unwrap()[0],
+            (id, item("Foo").tag("bugs").build())
+        );
+
+        assert!(matches!(
+            service.merge_tags(vec![bugs, bug], bug).await,
+            Err(ServiceError::Validation(ValidationError(fields))) if fields[0].field == "sourceIds"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tag_stats_and_prune() {
+        let service = service().await;
+        item("Foo").tag("a").save(&service).await;
+        item("Bar").tag("a").done().save(&service).await;
+        item("Baz").tag("a").done().save(&service).await;
+        let a = service.get_tag_id("a".into()).await.unwrap();
+        let b = service.create_tag("b".into()).await.unwrap();
+
+        assert_eq!(
+            service.fetch_tag_stats().await.unwrap(),
+            vec![
+                TagStats {
+                    id: a,
+                    value: "a".into(),
+                    open: 1,
+                    done: 2
+                },
+                TagStats {
+                    id: b,
+                    value: "b".into(),
+                    open: 0,
+                    done: 0
+                },
+            ]
+        );
+
+        let expect = vec![(b, "b".to_string())];
+        assert_eq!(service.prune_unused_tags(true).await.unwrap(), expect);
+        assert_eq!(service.fetch_all_tags().await.unwrap().len(), 2);
+        assert_eq!(service.prune_unused_tags(false).await.unwrap(), expect);
+        assert_eq!(service.fetch_all_tags().await.unwrap().len(), 1);
+    }
+}
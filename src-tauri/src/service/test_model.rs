@@ -0,0 +1,252 @@
+//! model based test, every operate apply both on the service and a simple in memory model,
+//! then the state of the database must match the model
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use proptest::prelude::*;
+
+use super::{
+    test_service::{item, service},
+    EditMode, PriorityLevel, ServiceError, TodoItem, TodoService,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+struct ModelItem {
+    message: String,
+    priority: PriorityLevel,
+    done: bool,
+    tags: BTreeSet<String>,
+}
+
+#[derive(Debug, Default)]
+struct Model {
+    items: BTreeMap<i32, ModelItem>,
+    tags: BTreeSet<String>,
+}
+
+/// operates on exist items use the index of the item in the model
+#[derive(Debug, Clone)]
+enum Operate {
+    Save {
+        message: String,
+        priority: PriorityLevel,
+        done: bool,
+        tags: BTreeSet<String>,
+    },
+    EditMessage(usize, String),
+    EditPriority(usize, PriorityLevel),
+    RevertDone(usize),
+    AddTag(usize, String),
+    RemoveTag(usize, String),
+    CleanTag(usize),
+    Delete(usize),
+}
+
+fn priority_level() -> impl Strategy<Value = PriorityLevel> {
+    prop_oneof![
+        Just(PriorityLevel::VeryHigh),
+        Just(PriorityLevel::High),
+        Just(PriorityLevel::Medium),
+        Just(PriorityLevel::Low),
+        Just(PriorityLevel::VeryLow),
+    ]
+}
+
+fn tag() -> impl Strategy<Value = String> {
+    // a small set of tags, so that the operates share tags
+    "[a-d]"
+}
+
+fn operate() -> impl Strategy<Value = Operate> {
+    prop_oneof![
+        (
+            ".*",
+            priority_level(),
+            any::<bool>(),
+            prop::collection::btree_set(tag(), 0..3)
+        )
+            .prop_map(|(message, priority, done, tags)| Operate::Save {
+                message,
+                priority,
+                done,
+                tags
+            }),
+        (any::<usize>(), ".*").prop_map(|(idx, msg)| Operate::EditMessage(idx, msg)),
+        (any::<usize>(), priority_level()).prop_map(|(idx, p)| Operate::EditPriority(idx, p)),
+        any::<usize>().prop_map(Operate::RevertDone),
+        (any::<usize>(), tag()).prop_map(|(idx, tag)| Operate::AddTag(idx, tag)),
+        (any::<usize>(), tag()).prop_map(|(idx, tag)| Operate::RemoveTag(idx, tag)),
+        any::<usize>().prop_map(Operate::CleanTag),
+        any::<usize>().prop_map(Operate::Delete),
+    ]
+}
+
+impl Model {
+    fn item_id(&self, idx: usize) -> Option<i32> {
+        if self.items.is_empty() {
+            None
+        } else {
+            self.items.keys().nth(idx % self.items.len()).copied()
+        }
+    }
+
+    fn item_mut(&mut self, idx: usize) -> Option<(i32, &mut ModelItem)> {
+        let id = self.item_id(idx)?;
+        self.items.get_mut(&id).map(|item| (id, item))
+    }
+}
+
+async fn apply(service: &TodoService, model: &mut Model, operate: Operate) {
+    match operate {
+        Operate::Save {
+            message,
+            priority,
+            done,
+            tags,
+        } => {
+            let mut fixture = tags
+                .iter()
+                .fold(item(&message).priority(priority), |fixture, tag| {
+                    fixture.tag(tag)
+                });
+            if done {
+                fixture = fixture.done();
+            }
+            let resp = service.save_todo_item(fixture.build()).await;
+            // the blank message is rejected and nothing is saved
+            if message.trim().is_empty() {
+                assert!(matches!(resp, Err(ServiceError::Validation(_))));
+                return;
+            }
+            let id = resp.unwrap();
+            model.tags.extend(tags.iter().cloned());
+            model.items.insert(
+                id,
+                ModelItem {
+                    message,
+                    priority,
+                    done,
+                    tags,
+                },
+            );
+        }
+        Operate::EditMessage(idx, message) => {
+            if let Some((id, item)) = model.item_mut(idx) {
+                let resp = service.edit_message(id, message.clone()).await;
+                if message.trim().is_empty() {
+                    assert!(matches!(resp, Err(ServiceError::Validation(_))));
+                } else {
+                    resp.unwrap();
+                    item.message = message;
+                }
+            }
+        }
+        Operate::EditPriority(idx, priority) => {
+            if let Some((id, item)) = model.item_mut(idx) {
+                service.edit_priority(id, priority).await.unwrap();
+                item.priority = priority;
+            }
+        }
+        Operate::RevertDone(idx) => {
+            if let Some((id, item)) = model.item_mut(idx) {
+                service.revert_done(id).await.unwrap();
+                item.done = !item.done;
+            }
+        }
+        Operate::AddTag(idx, tag) => {
+            if let Some((id, item)) = model.item_mut(idx) {
+                // bind a tag twice keeps only one bind, which `check` makes sure
+                service
+                    .edit_tag(id, EditMode::Add, tag.clone())
+                    .await
+                    .unwrap();
+                item.tags.insert(tag.clone());
+                model.tags.insert(tag);
+            }
+        }
+        Operate::RemoveTag(idx, tag) => {
+            if let Some((id, item)) = model.item_mut(idx) {
+                service
+                    .edit_tag(id, EditMode::Remove, tag.clone())
+                    .await
+                    .unwrap();
+                item.tags.remove(&tag);
+                model.tags.insert(tag);
+            }
+        }
+        Operate::CleanTag(idx) => {
+            if let Some((id, item)) = model.item_mut(idx) {
+                service.clean_tag(id).await.unwrap();
+                item.tags.clear();
+            }
+        }
+        Operate::Delete(idx) => {
+            if let Some(id) = model.item_id(idx) {
+                service.delete_todo_item(id).await.unwrap();
+                model.items.remove(&id);
+            }
+        }
+    }
+}
+
+async fn check(service: &TodoService, model: &Model) {
+    let items = service
+        .fetch_all_todo_item()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(
+            |(
+                id,
+                TodoItem {
+                    message,
+                    priority,
+                    done,
+                    tags,
+                    ..
+                },
+            )| {
+                let len = tags.len();
+                let tags = tags.into_iter().collect::<BTreeSet<_>>();
+                // no tag bind twice on one item
+                assert_eq!(len, tags.len());
+                (
+                    id,
+                    ModelItem {
+                        message,
+                        priority,
+                        done,
+                        tags,
+                    },
+                )
+            },
+        )
+        .collect::<BTreeMap<_, _>>();
+    assert_eq!(items, model.items);
+
+    let tags = service
+        .fetch_all_tags()
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|(_, tag, _)| tag)
+        .collect::<BTreeSet<_>>();
+    assert_eq!(tags, model.tags);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_model(operates in prop::collection::vec(operate(), 1..32)) {
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let service = service().await;
+            let mut model = Model::default();
+
+            for operate in operates {
+                apply(&service, &mut model, operate).await;
+                check(&service, &model).await;
+            }
+        });
+    }
+}
@@ -0,0 +1,366 @@
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+use crate::database::models::{
+    tag_item_bind::{BindEntity, BindModel},
+    tags::{TagEntity, TagModel},
+    time_entry::{TimeEntryEntity, TimeEntryModel},
+    todo_item::TodoItemEntity,
+};
+
+use super::{
+    validate::Validator, FieldError, OrNotFound, ServiceError, ServiceResult, TimeEntry, TimeTotal,
+    TimeTotals, TodoService, ValidationError,
+};
+
+impl TodoService {
+    /// start the timer on the item, the timer running on another item is stopped first.
+    /// starting the running timer again changes nothing
+    pub async fn start_timer(&self, item_id: i32) -> ServiceResult<TimeEntry> {
+        let mut tx = self.pool().begin().await?;
+        if TodoItemEntity::find_all_by_id(&mut tx, [item_id])
+            .await?
+            .is_empty()
+        {
+            return Err(ServiceError::ItemNotFound(item_id));
+        }
+
+        let now = Utc::now();
+        let id = match TimeEntryEntity::find_running(&mut tx).await? {
+            Some(running) if running.item_id == item_id => running.id,
+            running => {
+                if let Some(running) = running {
+                    TimeEntryEntity::update(&mut tx, running.id, running.started_at, Some(now))
+                        .await?;
+                }
+                TimeEntryEntity::save(&mut tx, item_id, now, None).await?
+            }
+        };
+        let model = TimeEntryEntity::find_by_id(&mut tx, id).await?;
+        tx.commit().await?;
+        Ok(Self::to_time_entry(model, now))
+    }
+
+    /// stop the running timer, return the stopped entry or none if no timer is running
+    pub async fn stop_timer(&self) -> ServiceResult<Option<TimeEntry>> {
+        let mut tx = self.pool().begin().await?;
+        let mut running = match TimeEntryEntity::find_running(&mut tx).await? {
+            Some(running) => running,
+            None => return Ok(None),
+        };
+        let now = Utc::now();
+        TimeEntryEntity::update(&mut tx, running.id, running.started_at, Some(now)).await?;
+        tx.commit().await?;
+
+        running.stopped_at = Some(now);
+        Ok(Some(Self::to_time_entry(running, now)))
+    }
+
+    pub async fn running_timer(&self) -> ServiceResult<Option<TimeEntry>> {
+        Ok(TimeEntryEntity::find_running(self.pool())
+            .await?
+            .map(|model| Self::to_time_entry(model, Utc::now())))
+    }
+
+    /// log a span of time spent on the item by hand
+    pub async fn add_time_entry(
+        &self,
+        item_id: i32,
+        started_at: DateTime<Utc>,
+        stopped_at: DateTime<Utc>,
+    ) -> ServiceResult<TimeEntry> {
+        check_span(started_at, Some(stopped_at))?;
+        self.ensure_item(item_id).await?;
+        let pool = self.pool();
+        let id = TimeEntryEntity::save(pool, item_id, started_at, Some(stopped_at)).await?;
+        let model = TimeEntryEntity::find_by_id(pool, id).await?;
+        Ok(Self::to_time_entry(model, Utc::now()))
+    }
+
+    /// change the span of the entry, `stopped_at` can only be none for the running timer
+    pub async fn edit_time_entry(
+        &self,
+        entry_id: i32,
+        started_at: DateTime<Utc>,
+        stopped_at: Option<DateTime<Utc>>,
+    ) -> ServiceResult<()> {
+        check_span(started_at, stopped_at)?;
+        let mut tx = self.pool().begin().await?;
+        let old = TimeEntryEntity::find_by_id(&mut tx, entry_id)
+            .await
+            .or_not_found(|| ServiceError::TimeEntryNotFound(entry_id))?;
+        // a stopped entry can not run again, there would be two running timers
+        if stopped_at.is_none() && old.stopped_at.is_some() {
+            return Err(ValidationError(vec![FieldError {
+                field: "stoppedAt".into(),
+                message: "must be set for a stopped entry".into(),
+            }])
+            .into());
+        }
+        TimeEntryEntity::update(&mut tx, entry_id, started_at, stopped_at).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn delete_time_entry(&self, entry_id: i32) -> ServiceResult<()> {
+        TimeEntryEntity::remove(self.pool(), entry_id)
+            .await
+            .or_not_found(|| ServiceError::TimeEntryNotFound(entry_id))
+    }
+
+    /// the time logged on the item, the earliest started first
+    pub async fn fetch_time_entries(&self, item_id: i32) -> ServiceResult<Vec<TimeEntry>> {
+        self.ensure_item(item_id).await?;
+        let now = Utc::now();
+        Ok(TimeEntryEntity::fetch_all_by_item_id(self.pool(), item_id)
+            .await?
+            .into_iter()
+            .map(|model| Self::to_time_entry(model, now))
+            .collect())
+    }
+
+    /// sum the time logged by item, by tag and by day. the days are in the time zone
+    /// `utc_offset` minutes east of UTC
+    pub async fn fetch_time_totals(&self, utc_offset: i32) -> ServiceResult<TimeTotals> {
+        Validator::new(self.limits())
+            .utc_offset("utcOffset", utc_offset)
+            .finish()?;
+        let pool = self.pool();
+        let now = Utc::now();
+        let shift = Duration::minutes(utc_offset.into());
+
+        let mut by_item = BTreeMap::<i32, i64>::new();
+        let mut by_day = BTreeMap::<NaiveDate, i64>::new();
+        for TimeEntryModel {
+            item_id,
+            started_at,
+            stopped_at,
+            ..
+        } in TimeEntryEntity::fetch_all(pool).await?
+        {
+            let stopped_at = stopped_at.unwrap_or(now);
+            *by_item.entry(item_id).or_default() += seconds(started_at, stopped_at);
+
+            let (mut from, to) = (
+                started_at.naive_utc() + shift,
+                stopped_at.naive_utc() + shift,
+            );
+            while from < to {
+                let midnight = (from.date() + Duration::days(1)).and_hms_opt(0, 0, 0);
+                let until = midnight.map_or(to, |midnight| midnight.min(to));
+                *by_day.entry(from.date()).or_default() += (until - from).num_seconds();
+                from = until;
+            }
+        }
+
+        let tags = TagEntity::fetch_all(pool, None)
+            .await?
+            .into_iter()
+            .map(|TagModel { id, value, .. }| (id, value))
+            .collect::<HashMap<_, _>>();
+        let mut by_tag = BTreeMap::new();
+        for BindModel { tag_id, item_id } in BindEntity::fetch_all(pool).await? {
+            if let (Some(tag), Some(seconds)) = (tags.get(&tag_id), by_item.get(&item_id)) {
+                *by_tag.entry(tag.clone()).or_default() += seconds;
+            }
+        }
+
+        Ok(TimeTotals {
+            by_item: to_totals(by_item),
+            by_tag: to_totals(by_tag),
+            by_day: to_totals(by_day),
+        })
+    }
+
+    fn to_time_entry(
+        TimeEntryModel {
+            id,
+            item_id,
+            started_at,
+            stopped_at,
+        }: TimeEntryModel,
+        now: DateTime<Utc>,
+    ) -> TimeEntry {
+        TimeEntry {
+            id,
+            item_id,
+            started_at,
+            stopped_at,
+            seconds: seconds(started_at, stopped_at.unwrap_or(now)),
+        }
+    }
+}
+
+fn seconds(from: DateTime<Utc>, to: DateTime<Utc>) -> i64 {
+    (to - from).num_seconds().max(0)
+}
+
+fn to_totals<K>(map: BTreeMap<K, i64>) -> Vec<TimeTotal<K>> {
+    map.into_iter()
+        .map(|(key, seconds)| TimeTotal { key, seconds })
+        .collect()
+}
+
+fn check_span(started_at: DateTime<Utc>, stopped_at: Option<DateTime<Utc>>) -> ServiceResult<()> {
+    match stopped_at {
+        Some(stopped_at) if stopped_at < started_at => Err(ValidationError(vec![FieldError {
+            field: "stoppedAt".into(),
+            message: "must not be before `startedAt`".into(),
+        }])
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test_time_entry {
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+
+    use crate::service::{
+        test_service::{item, service},
+        ServiceError, TimeTotal,
+    };
+
+    #[tokio::test]
+    async fn test_timer() {
+        let service = service().await;
+        let foo = item("Foo").save(&service).await;
+        let bar = item("Bar").save(&service).await;
+        assert!(service.stop_timer().await.unwrap().is_none());
+
+        let first = service.start_timer(foo).await.unwrap();
+        assert_eq!(service.start_timer(foo).await.unwrap().id, first.id);
+        // start another one stops the running timer
+        let second = service.start_timer(bar).await.unwrap();
+        assert_eq!(
+            service.running_timer().await.unwrap().map(|entry| entry.id),
+            Some(second.id)
+        );
+        let entries = service.fetch_time_entries(foo).await.unwrap();
+        assert!(entries[0].stopped_at.is_some());
+
+        let stopped = service.stop_timer().await.unwrap().unwrap();
+        assert_eq!(stopped.id, second.id);
+        assert!(stopped.stopped_at.is_some());
+        assert!(service.running_timer().await.unwrap().is_none());
+
+        assert!(service
+            .start_timer(bar + 1)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        service.delete_todo_item(foo).await.unwrap();
+        assert!(service
+            .delete_time_entry(first.id)
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_edit() {
+        let service = service().await;
+        let foo = item("Foo").save(&service).await;
+        let start = Utc.ymd(2022, 10, 1).and_hms(9, 0, 0);
+
+        assert!(matches!(
+            service
+                .add_time_entry(foo, start, start - Duration::minutes(1))
+                .await,
+            Err(ServiceError::Validation(_))
+        ));
+        let entry = service
+            .add_time_entry(foo, start, start + Duration::minutes(30))
+            .await
+            .unwrap();
+        assert_eq!(entry.seconds, 30 * 60);
+
+        service
+            .edit_time_entry(entry.id, start, Some(start + Duration::hours(1)))
+            .await
+            .unwrap();
+        assert_eq!(
+            service.fetch_time_entries(foo).await.unwrap()[0].seconds,
+            60 * 60
+        );
+        // a stopped entry can not run again
+        assert!(matches!(
+            service.edit_time_entry(entry.id, start, None).await,
+            Err(ServiceError::Validation(_))
+        ));
+
+        service.delete_time_entry(entry.id).await.unwrap();
+        assert!(service
+            .edit_time_entry(entry.id, start, Some(start))
+            .await
+            .unwrap_err()
+            .is_not_found());
+    }
+
+    #[tokio::test]
+    async fn test_totals() {
+        let service = service().await;
+        let foo = item("Foo").tag("work").save(&service).await;
+        let bar = item("Bar").tags(&["work", "home"]).save(&service).await;
+        let at = |day: u32, hour: u32| Utc.ymd(2022, 10, day).and_hms(hour, 0, 0);
+
+        service
+            .add_time_entry(foo, at(1, 9), at(1, 10))
+            .await
+            .unwrap();
+        // across the midnight in UTC
+        service
+            .add_time_entry(bar, at(1, 23), at(2, 1))
+            .await
+            .unwrap();
+
+        let totals = service.fetch_time_totals(0).await.unwrap();
+        let hours = |seconds: i64| seconds / 3600;
+        assert_eq!(
+            totals
+                .by_item
+                .iter()
+                .map(|total| (total.key, hours(total.seconds)))
+                .collect::<Vec<_>>(),
+            [(foo, 1), (bar, 2)]
+        );
+        assert_eq!(
+            totals.by_tag,
+            [
+                TimeTotal {
+                    key: "home".to_string(),
+                    seconds: 2 * 3600
+                },
+                TimeTotal {
+                    key: "work".to_string(),
+                    seconds: 3 * 3600
+                }
+            ]
+        );
+        let day = |day: u32| NaiveDate::from_ymd(2022, 10, day);
+        assert_eq!(
+            totals
+                .by_day
+                .iter()
+                .map(|total| (total.key, hours(total.seconds)))
+                .collect::<Vec<_>>(),
+            [(day(1), 2), (day(2), 1)]
+        );
+
+        // two hours east of UTC all the time is on the second day
+        let totals = service.fetch_time_totals(120).await.unwrap();
+        assert_eq!(
+            totals
+                .by_day
+                .iter()
+                .map(|total| (total.key, hours(total.seconds)))
+                .collect::<Vec<_>>(),
+            [(day(1), 1), (day(2), 2)]
+        );
+        assert!(matches!(
+            service.fetch_time_totals(24 * 60).await,
+            Err(ServiceError::Validation(_))
+        ));
+    }
+}
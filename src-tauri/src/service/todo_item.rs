@@ -0,0 +1,616 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use sqlx::SqliteConnection;
+use tap::Tap;
+
+use crate::database::models::attachment::AttachmentEntity;
+use crate::database::models::comment::CommentEntity;
+use crate::database::models::dependency::DependencyEntity;
+use crate::database::models::item_event::{ItemChange, ItemEventEntity};
+use crate::database::models::tag_item_bind::BindEntity;
+use crate::database::models::tag_item_bind::BindModel;
+use crate::database::models::tags::TagEntity;
+use crate::database::models::tags::TagModel;
+use crate::database::models::time_entry::TimeEntryEntity;
+use crate::database::models::todo_item::TodoItemEntity;
+use crate::database::models::todo_item::TodoItemModel;
+use crate::database::rank;
+
+use super::estimate;
+use super::validate::Validator;
+use super::{
+    DoneState, EditMode, FieldError, OrNotFound, PriorityLevel, ServiceError, ServiceResult, Tag,
+    TodoItem, TodoService, ValidationError,
+};
+
+impl TodoService {
+    pub async fn save_todo_item(
+        &self,
+        TodoItem {
+            message,
+            priority,
+            done,
+            estimate,
+            due,
+            tags,
+            ..
+        }: TodoItem,
+    ) -> ServiceResult<i32> {
+        Validator::new(self.limits())
+            .message("message", &message)
+            .tags("tags", &tags)
+            .finish()?;
+        // the tags, the item and the binds are saved all or nothing
+        let mut tx = self.pool().begin().await?;
+        // save tags
+        let mut ids = Vec::with_capacity(tags.len());
+        for tag in tags {
+            ids.push(TagEntity::save_in(&mut tx, self.tag_policy(), tag).await?);
+        }
+
+        // save todo item
+        let todo_item_id = TodoItemEntity::save_in(&mut tx, message, priority.into(), done).await?;
+        if estimate.is_some() {
+            let (minutes, points) = estimate::to_columns(estimate);
+            TodoItemEntity::update_estimate(&mut tx, todo_item_id, minutes, points).await?;
+        }
+        if due.is_some() {
+            TodoItemEntity::update_due_on(&mut tx, todo_item_id, due).await?;
+        }
+        // bind tags with items
+        BindEntity::save_all(
+            &mut tx,
+            ids.into_iter()
+                .map(|tag_id| BindModel::new(tag_id, todo_item_id)),
+        )
+        .await?;
+        tx.commit().await?;
+        // return the id of this todo item
+        Ok(todo_item_id)
+    }
+
+    pub async fn fetch_all_todo_item(&self) -> ServiceResult<Vec<(i32, TodoItem)>> {
+        let pool = self.pool();
+        // fetch all tags
+        let all_tags = TagEntity::fetch_all(pool, None)
+            .await?
+            .into_iter()
+            .map(|TagModel { id, value, .. }| (id, value))
+            .collect::<HashMap<_, _>>();
+        // fetch all tags-item bind;
+        let mut all_binds = BindEntity::fetch_all(pool).await?.into_iter().fold(
+            HashMap::<i32, Vec<i32>>::new(),
+            |mut map, BindModel { tag_id, item_id }| {
+                map.entry(item_id)
+                    .and_modify(|v| v.push(tag_id))
+                    .or_insert_with(|| vec![tag_id]);
+                map
+            },
+        );
+        let blocked = self.fetch_blocked_ids().await?;
+        // fetch all todo items with its id, then bind the tags and todo items
+        let all_todo_items = TodoItemEntity::fetch_all(pool, None)
+            .await?
+            .into_iter()
+            .map(|model| {
+                let is_blocked = blocked.contains(&model.id);
+                (model.id, Self::to_todo_item(model, is_blocked, vec![]))
+            })
+            .map(|item| {
+                item.tap_mut(|(item_id, item)| {
+                    if let Some(vec) = all_binds.remove(item_id) {
+                        item.tags.extend(
+                            vec.into_iter()
+                                .filter_map(|tag_id| all_tags.get(&tag_id))
+                                .cloned(),
+                        )
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(all_todo_items)
+    }
+
+    pub async fn edit_message(&self, item_id: i32, message: String) -> ServiceResult<()> {
+        Validator::new(self.limits())
+            .message("newMessage", &message)
+            .finish()?;
+        TodoItemEntity::update_message(self.pool(), item_id, message)
+            .await
+            .or_not_found(|| ServiceError::ItemNotFound(item_id))?;
+        Ok(())
+    }
+
+    /// set or clear the day the item should be done by
+    pub async fn edit_due(&self, item_id: i32, due: Option<NaiveDate>) -> ServiceResult<()> {
+        TodoItemEntity::update_due_on(self.pool(), item_id, due)
+            .await
+            .or_not_found(|| ServiceError::ItemNotFound(item_id))
+    }
+
+    pub async fn edit_priority(&self, item_id: i32, priority: PriorityLevel) -> ServiceResult<()> {
+        let mut tx = self.pool().begin().await?;
+        let old = TodoItemEntity::find_all_by_id(&mut tx, [item_id])
+            .await?
+            .pop()
+            .ok_or(ServiceError::ItemNotFound(item_id))?;
+        let priority = priority.into();
+        TodoItemEntity::update_priority(&mut tx, item_id, priority).await?;
+        if old.priority != priority {
+            let change = ItemChange::Priority {
+                from: old.priority,
+                to: priority,
+            };
+            ItemEventEntity::save_all(&mut tx, [(item_id, change)]).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn revert_done(&self, item_id: i32) -> ServiceResult<()> {
+        let mut tx = self.pool().begin().await?;
+        let old = TodoItemEntity::find_all_by_id(&mut tx, [item_id])
+            .await?
+            .pop()
+            .ok_or(ServiceError::ItemNotFound(item_id))?;
+        if !old.done {
+            Self::ensure_unblocked(&mut tx, item_id).await?;
+        }
+        TodoItemEntity::revert_done(&mut tx, item_id).await?;
+        ItemEventEntity::save_all(&mut tx, [(item_id, ItemChange::Done(!old.done))]).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// set the done state, calling it again with the same state changes nothing
+    pub async fn set_done(&self, item_id: i32, done: bool) -> ServiceResult<DoneState> {
+        let mut tx = self.pool().begin().await?;
+        let old = TodoItemEntity::find_all_by_id(&mut tx, [item_id])
+            .await?
+            .pop();
+        if done && matches!(old, Some(ref old) if !old.done) {
+            Self::ensure_unblocked(&mut tx, item_id).await?;
+        }
+        let rows_affected = TodoItemEntity::set_done(&mut tx, item_id, done).await?;
+        if matches!(old, Some(old) if old.done != done) {
+            ItemEventEntity::save_all(&mut tx, [(item_id, ItemChange::Done(done))]).await?;
+        }
+        tx.commit().await?;
+        Ok(DoneState {
+            done,
+            rows_affected,
+        })
+    }
+
+    /// add or remove a tag on the todo item, return the id of the tag
+    pub async fn edit_tag(
+        &self,
+        item_id: i32,
+        mode: EditMode,
+        tag_name: Tag,
+    ) -> ServiceResult<i32> {
+        Validator::new(self.limits())
+            .tag("tagName", &tag_name)
+            .finish()?;
+        let pool = self.pool();
+        self.ensure_item(item_id).await?;
+        let tag_id = TagEntity::save(pool, self.tag_policy(), &tag_name).await?;
+
+        match mode {
+            // adding tag , create tag first then bind to the todo item
+            EditMode::Add => BindEntity::save_all(pool, [BindModel::new(tag_id, item_id)]).await?,
+            // remove tag , remove the bind between the tag and todo item
+            EditMode::Remove => BindEntity::remove(pool, tag_id, item_id).await?,
+        }
+        Ok(tag_id)
+    }
+
+    /// move the item between the two neighbours in the manual order, `None` for the start
+    /// or the end of the list. only the moved item is updated
+    pub async fn move_item(
+        &self,
+        item_id: i32,
+        before_id: Option<i32>,
+        after_id: Option<i32>,
+    ) -> ServiceResult<()> {
+        let mut fields = vec![];
+        for (field, id) in [("beforeId", before_id), ("afterId", after_id)] {
+            if id == Some(item_id) {
+                fields.push(FieldError {
+                    field: field.into(),
+                    message: "must not be the moved item".into(),
+                });
+            }
+        }
+        if !fields.is_empty() {
+            return Err(ValidationError(fields).into());
+        }
+
+        let mut tx = self.pool().begin().await?;
+        let neighbours =
+            TodoItemEntity::find_all_by_id(&mut tx, before_id.into_iter().chain(after_id)).await?;
+        let rank_of = |id: Option<i32>| -> ServiceResult<Option<String>> {
+            match id {
+                None => Ok(None),
+                Some(id) => neighbours
+                    .iter()
+                    .find(|model| model.id == id)
+                    .map(|model| model.rank.clone())
+                    .ok_or(ServiceError::ItemNotFound(id)),
+            }
+        };
+        let (before, after) = (rank_of(before_id)?, rank_of(after_id)?);
+        // the neighbours come from the list the caller saw, another item may be moved
+        // between them since then
+        if before.is_some() || after.is_some() {
+            let between = TodoItemEntity::count_between(
+                &mut tx,
+                item_id,
+                before.as_deref(),
+                after.as_deref(),
+            )
+            .await?;
+            if between > 0 {
+                let (field, message) = match (before_id, after_id) {
+                    (Some(_), Some(_)) => ("afterId", "must be right after `beforeId`"),
+                    (Some(_), None) => ("beforeId", "must be the last item"),
+                    _ => ("afterId", "must be the first item"),
+                };
+                return Err(ValidationError(vec![FieldError {
+                    field: field.into(),
+                    message: message.into(),
+                }])
+                .into());
+            }
+        }
+
+        let rank = match rank::between(before.as_deref(), after.as_deref()) {
+            Some(rank) => rank,
+            None => {
+                return Err(ValidationError(vec![FieldError {
+                    field: "afterId".into(),
+                    message: "must be after `beforeId`".into(),
+                }])
+                .into())
+            }
+        };
+        TodoItemEntity::update_rank(&mut tx, item_id, rank)
+            .await
+            .or_not_found(|| ServiceError::ItemNotFound(item_id))?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn clean_tag(&self, item_id: i32) -> ServiceResult<()> {
+        self.ensure_item(item_id).await?;
+        // remove all bind on todo item
+        BindEntity::remove_bind_item_id(self.pool(), item_id).await?;
+        Ok(())
+    }
+
+    pub async fn delete_todo_item(&self, item_id: i32) -> ServiceResult<()> {
+        let mut tx = self.pool().begin().await?;
+        // remove tag-item bind
+        BindEntity::remove_bind_item_ids(&mut tx, [item_id]).await?;
+        // remove the comments, the history, the time logged and the dependencies on both sides
+        CommentEntity::remove_all_by_item_id(&mut tx, [item_id]).await?;
+        DependencyEntity::remove_all_by_item_id(&mut tx, [item_id]).await?;
+        TimeEntryEntity::remove_all_by_item_id(&mut tx, [item_id]).await?;
+        ItemEventEntity::remove_all_by_item_id(&mut tx, [item_id]).await?;
+        // remove attachments, the blobs nobody use are removed after the commit
+        let hashes = AttachmentEntity::fetch_all_hash_by_item_id(&mut tx, [item_id]).await?;
+        AttachmentEntity::remove_all_by_item_id(&mut tx, [item_id]).await?;
+        // remove item
+        TodoItemEntity::remove(&mut tx, item_id)
+            .await
+            .or_not_found(|| ServiceError::ItemNotFound(item_id))?;
+        tx.commit().await?;
+        self.remove_unused_blobs(hashes).await
+    }
+
+    /// an item can not be done while any blocker is open
+    async fn ensure_unblocked(tx: &mut SqliteConnection, item_id: i32) -> ServiceResult<()> {
+        match Self::fetch_open_blockers(tx, vec![item_id])
+            .await?
+            .remove(&item_id)
+        {
+            Some(blockers) => Err(ServiceError::ItemBlocked { item_id, blockers }),
+            None => Ok(()),
+        }
+    }
+
+    /// the binds have no foreign key, check the item before touch them
+    pub(super) async fn ensure_item(&self, item_id: i32) -> ServiceResult<()> {
+        if TodoItemEntity::find_all_by_id(self.pool(), [item_id])
+            .await?
+            .is_empty()
+        {
+            return Err(ServiceError::ItemNotFound(item_id));
+        }
+        Ok(())
+    }
+
+    /// the todo items with their tags, in the order of the models
+    pub(super) async fn with_tags(
+        &self,
+        models: Vec<TodoItemModel>,
+    ) -> ServiceResult<Vec<(i32, TodoItem)>> {
+        let pool = self.pool();
+        let blocked = self.fetch_blocked_ids().await?;
+        let mut resp = Vec::new();
+        for model in models {
+            let tags = TagEntity::find_all_by_id(
+                pool,
+                BindEntity::fetch_all_tag_id(pool, model.id).await?,
+            )
+            .await?
+            .into_iter()
+            .map(|TagModel { value, .. }| value)
+            .collect();
+
+            let is_blocked = blocked.contains(&model.id);
+            resp.push((model.id, Self::to_todo_item(model, is_blocked, tags)))
+        }
+        Ok(resp)
+    }
+
+    pub(super) fn to_todo_item(
+        TodoItemModel {
+            message,
+            priority,
+            done,
+            estimate_minutes,
+            estimate_points,
+            due_on,
+            ..
+        }: TodoItemModel,
+        blocked: bool,
+        tags: Vec<Tag>,
+    ) -> TodoItem {
+        TodoItem {
+            message,
+            priority: priority.into(),
+            done,
+            blocked,
+            estimate: estimate::from_columns(estimate_minutes, estimate_points),
+            due: due_on,
+            tags,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_todo_item {
+    use crate::config::InputLimits;
+    use crate::service::{
+        test_service::{item, service},
+        DoneState, EditMode, PriorityLevel, ServiceError,
+    };
+
+    #[tokio::test]
+    async fn test_save_and_fetch() {
+        let service = service().await;
+        let foo = item("Foo").tags(&["a", "b"]).save(&service).await;
+        let bar = item("Bar").save(&service).await;
+
+        let items = service.fetch_all_todo_item().await.unwrap();
+
+        assert_eq!(
+            items,
+            vec![
+                (foo, item("Foo").tags(&["a", "b"]).build()),
+                (bar, item("Bar").build())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_edit() {
+        let service = service().await;
+        let id = item("Foo").save(&service).await;
+
+        service.edit_message(id, "Bar".into()).await.unwrap();
+        service
+            .edit_priority(id, PriorityLevel::High)
+            .await
+            .unwrap();
+        service.revert_done(id).await.unwrap();
+
+        let items = service.fetch_all_todo_item().await.unwrap();
+        assert_eq!(
+            items,
+            vec![(id, item("Bar").priority(PriorityLevel::High).done().build())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_set_done() {
+        let service = service().await;
+        let id = item("Foo").save(&service).await;
+
+        let state = DoneState {
+            done: true,
+            rows_affected: 1,
+        };
+        // set twice keep the item done
+        assert_eq!(service.set_done(id, true).await.unwrap(), state);
+        assert_eq!(service.set_done(id, true).await.unwrap(), state);
+        assert!(service.fetch_all_todo_item().await.unwrap()[0].1.done);
+
+        assert_eq!(
+            service.set_done(id + 1, false).await.unwrap(),
+            DoneState {
+                done: false,
+                rows_affected: 0
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_not_found() {
+        let service = service().await;
+        let id = item("Foo").save(&service).await;
+        let missing = id + 1;
+
+        let not_found = format!("Todo item {missing} not found");
+        let errors = [
+            service.edit_message(missing, "Bar".into()).await,
+            service.edit_priority(missing, PriorityLevel::High).await,
+            service.revert_done(missing).await,
+            service
+                .edit_tag(missing, EditMode::Add, "a".into())
+                .await
+                .map(|_| ()),
+            service.clean_tag(missing).await,
+            service.delete_todo_item(missing).await,
+        ];
+        for err in errors {
+            let err = err.unwrap_err();
+            assert!(err.is_not_found());
+            assert_eq!(err.to_string(), not_found);
+        }
+        // nothing is created for the missing item
+        assert!(service.fetch_all_tags().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_validation() {
+        let service = service().await.with_limits(InputLimits {
+            max_message_len: 3,
+            max_tag_len: 3,
+            ..Default::default()
+        });
+
+        let err = service
+            .save_todo_item(item(" ").tags(&["a", "abcd"]).build())
+            .await
+            .unwrap_err();
+        let fields = match err {
+            ServiceError::Validation(err) => err.0,
+            err => panic!("unexpected error {err}"),
+        };
+        assert_eq!(
+            fields
+                .iter()
+                .map(|field| field.field.as_str())
+                .collect::<Vec<_>>(),
+            ["message", "tags[1]"]
+        );
+        // nothing saved with the invalid input
+        assert!(service.fetch_all_todo_item().await.unwrap().is_empty());
+        assert!(service.fetch_all_tags().await.unwrap().is_empty());
+
+        let id = item("Foo").tag("a").save(&service).await;
+        let tag_id = service.get_tag_id("a".into()).await.unwrap();
+        let errors = [
+            service.edit_message(id, "Long".into()).await,
+            service
+                .edit_tag(id, EditMode::Add, "\n".into())
+                .await
+                .map(|_| ()),
+            service.create_tag("".into()).await.map(|_| ()),
+            service.rename_tag(tag_id, "abcd".into()).await,
+        ];
+        for err in errors {
+            assert!(matches!(err, Err(ServiceError::Validation(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_move_item() {
+        let service = service().await;
+        let mut ids = vec![];
+        for message in ["A", "B", "C", "D"] {
+            ids.push(item(message).save(&service).await);
+        }
+        let (a, b, c, d) = (ids[0], ids[1], ids[2], ids[3]);
+        let order = || async {
+            service
+                .fetch_all_todo_item()
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(_, item)| item.message)
+                .collect::<Vec<_>>()
+                .join("")
+        };
+
+        service.move_item(d, Some(a), Some(b)).await.unwrap();
+        assert_eq!(order().await, "ADBC");
+        service.move_item(a, None, Some(d)).await.unwrap();
+        assert_eq!(order().await, "ADBC");
+        service.move_item(a, Some(c), None).await.unwrap();
+        assert_eq!(order().await, "DBCA");
+        service.move_item(c, None, Some(d)).await.unwrap();
+        assert_eq!(order().await, "CDBA");
+
+        // the neighbours out of order, the item itself as neighbour or a missing one
+        let errors = [
+            service.move_item(c, Some(a), Some(d)).await,
+            service.move_item(c, Some(c), None).await,
+        ];
+        for err in errors {
+            assert!(matches!(err, Err(ServiceError::Validation(_))));
+        }
+        let missing = d + 1;
+        assert!(service
+            .move_item(c, Some(missing), None)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert!(service
+            .move_item(missing, Some(a), None)
+            .await
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(order().await, "CDBA");
+
+        // the neighbours are stale once another item is moved between them
+        for (before, after) in [(Some(c), Some(a)), (Some(c), None), (None, Some(d))] {
+            let err = service.move_item(b, before, after).await.unwrap_err();
+            assert!(matches!(err, ServiceError::Validation(_)));
+        }
+        assert_eq!(order().await, "CDBA");
+    }
+
+    #[tokio::test]
+    async fn test_edit_tag() {
+        let service = service().await;
+        let id = item("Foo").tag("a").save(&service).await;
+
+        service
+            .edit_tag(id, EditMode::Add, "b".into())
+            .await
+            .unwrap();
+        service
+            .edit_tag(id, EditMode::Remove, "a".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            service.fetch_all_todo_item().await.unwrap(),
+            vec![(id, item("Foo").tag("b").build())]
+        );
+
+        service.clean_tag(id).await.unwrap();
+        assert_eq!(
+            service.fetch_all_todo_item().await.unwrap(),
+            vec![(id, item("Foo").build())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete() {
+        let service = service().await;
+        let id = item("Foo").tag("a").save(&service).await;
+
+        service.delete_todo_item(id).await.unwrap();
+
+        assert!(service.fetch_all_todo_item().await.unwrap().is_empty());
+        // the tag is kept after the item removed
+        let tags = service.fetch_all_tags().await.unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].1, "a");
+    }
+}
@@ -0,0 +1,195 @@
+//! check the user input before it reach the database
+
+use std::fmt;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::config::InputLimits;
+
+/// one invalid field of the input, `field` is named as the argument of the command,
+/// with the index for the items of a list, like `tags[1]`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// all the invalid fields of one input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+pub struct ValidationError(pub Vec<FieldError>);
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = self
+            .0
+            .iter()
+            .map(|FieldError { field, message }| format!("`{field}` {message}"))
+            .collect::<Vec<_>>();
+        write!(f, "Invalid input: {}", fields.join(", "))
+    }
+}
+
+/// collect the errors of all fields, so the user can fix them at once
+pub(super) struct Validator<'a> {
+    limits: &'a InputLimits,
+    errors: Vec<FieldError>,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(limits: &'a InputLimits) -> Self {
+        Self {
+            limits,
+            errors: vec![],
+        }
+    }
+
+    pub fn message(self, field: impl Into<String>, message: &str) -> Self {
+        let max = self.limits.max_message_len;
+        self.text(field.into(), message, max)
+    }
+
+    /// the notes can be empty
+    pub fn notes(mut self, field: impl Into<String>, notes: &str) -> Self {
+        let max = self.limits.max_notes_len;
+        if notes.chars().count() > max {
+            self.push(field.into(), format!("must be at most {max} characters"));
+        }
+        self
+    }
+
+    pub fn tag(mut self, field: impl Into<String>, tag: &str) -> Self {
+        let field = field.into();
+        if tag.chars().any(char::is_control) {
+            self.push(field, "must not contain control characters".into());
+            return self;
+        }
+        let max = self.limits.max_tag_len;
+        self.text(field, tag.trim(), max)
+    }
+
+    pub fn tags<'t>(self, field: &str, tags: impl IntoIterator<Item = &'t String>) -> Self {
+        tags.into_iter().enumerate().fold(self, |this, (idx, tag)| {
+            this.tag(format!("{field}[{idx}]"), tag)
+        })
+    }
+
+    /// the color of a tag is a hex color like `#ff8800`, no color is fine
+    pub fn color(mut self, field: impl Into<String>, color: Option<&str>) -> Self {
+        if let Some(color) = color {
+            let hex = color.strip_prefix('#').unwrap_or_default();
+            if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                self.push(field.into(), "must be a hex color like `#ff8800`".into());
+            }
+        }
+        self
+    }
+
+    /// the minutes east of UTC, the real offsets are within a day
+    pub fn utc_offset(mut self, field: impl Into<String>, minutes: i32) -> Self {
+        if minutes.abs() >= 24 * 60 {
+            self.push(field.into(), "must be less than a day".into());
+        }
+        self
+    }
+
+    /// the days of an inclusive range, the error is on the end of it
+    pub fn date_range(mut self, field: impl Into<String>, from: NaiveDate, to: NaiveDate) -> Self {
+        if to < from {
+            self.push(field.into(), format!("must not be before {from}"));
+        }
+        self
+    }
+
+    pub fn finish(self) -> Result<(), ValidationError> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError(self.errors))
+        }
+    }
+
+    fn text(mut self, field: String, text: &str, max: usize) -> Self {
+        if text.trim().is_empty() {
+            self.push(field, "must not be empty".into());
+        } else if text.chars().count() > max {
+            self.push(field, format!("must be at most {max} characters"));
+        }
+        self
+    }
+
+    fn push(&mut self, field: String, message: String) {
+        self.errors.push(FieldError { field, message });
+    }
+}
+
+#[cfg(test)]
+mod test_validate {
+    use crate::config::InputLimits;
+
+    use super::{FieldError, Validator};
+
+    #[test]
+    fn test_validator() {
+        let limits = InputLimits {
+            max_message_len: 4,
+            max_tag_len: 2,
+            max_notes_len: 4,
+        };
+        assert!(Validator::new(&limits)
+            .message("message", "Foo")
+            .tags("tags", &["a".into(), " ab ".into()])
+            .finish()
+            .is_ok());
+
+        let err = Validator::new(&limits)
+            .message("message", " \n ")
+            .tags("tags", &["a".into(), "abc".into(), "a\tb".into()])
+            .finish()
+            .unwrap_err();
+        let field = |field: &str, message: &str| FieldError {
+            field: field.into(),
+            message: message.into(),
+        };
+        assert_eq!(
+            err.0,
+            vec![
+                field("message", "must not be empty"),
+                field("tags[1]", "must be at most 2 characters"),
+                field("tags[2]", "must not contain control characters"),
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "Invalid input: `message` must not be empty, `tags[1]` must be at most 2 characters, \
+             `tags[2]` must not contain control characters"
+        );
+
+        // the length counts chars rather than bytes
+        assert!(Validator::new(&limits)
+            .message("message", "éééé")
+            .notes("notes", "")
+            .finish()
+            .is_ok());
+        assert!(Validator::new(&limits)
+            .notes("notes", "éééée")
+            .finish()
+            .is_err());
+
+        assert!(Validator::new(&limits)
+            .color("color", Some("#Ff8800"))
+            .color("color", None)
+            .finish()
+            .is_ok());
+        for color in ["ff8800", "#ff880", "#ff88000", "#gg8800", "red"] {
+            assert_eq!(
+                Validator::new(&limits)
+                    .color("color", Some(color))
+                    .finish()
+                    .unwrap_err()
+                    .0,
+                vec![field("color", "must be a hex color like `#ff8800`")]
+            );
+        }
+    }
+}
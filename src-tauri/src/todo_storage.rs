@@ -1,295 +1,554 @@
-use std::collections::HashMap;
-
-use futures::StreamExt;
-use futures::TryStreamExt;
-use serde::{Deserialize, Serialize};
-use sqlx::SqlitePool;
-use tap::Pipe;
-use tap::Tap;
+use std::path::PathBuf;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
 use tauri::command;
 use tauri::State;
 
-use crate::database::models::tag_item_bind::BindEntity;
-use crate::database::models::tag_item_bind::BindModel;
-use crate::database::models::tags::TagEntity;
-use crate::database::models::tags::TagModel;
-use crate::database::models::todo_item::TodoItemEntity;
-use crate::database::models::todo_item::TodoItemModel;
-use crate::util::ErrMapString;
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TodoItem {
-    message: String,
-    priority: PriorityLevel,
-    done: bool,
-    tags: Vec<Tag>,
-}
+pub use crate::service::{
+    Activity, Attachment, BulkOperate, BulkResult, BulkTarget, Comment, DoneState, EditMode,
+    Estimate, EstimateRollup, FieldError, ItemFilter, PriorityLevel, SavedSearch, SearchFilter,
+    Stats, Tag, TagMeta, TagNode, TagStats, TimeEntry, TimeTotals, TodoItem,
+};
+use crate::service::{ServiceError, TodoService};
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
-pub enum PriorityLevel {
-    VeryHigh,
-    High,
-    Medium,
-    Low,
-    VeryLow,
+/// the error of all the commands, the frontend shows the `fields` next to the inputs,
+/// which is empty for the errors other than validation
+#[derive(Debug, Serialize)]
+pub struct CommandError {
+    pub message: String,
+    pub fields: Vec<FieldError>,
 }
 
-pub type Tag = String;
+impl From<ServiceError> for CommandError {
+    fn from(err: ServiceError) -> Self {
+        let message = err.to_string();
+        let fields = match err {
+            ServiceError::Validation(err) => err.0,
+            _ => vec![],
+        };
+        Self { message, fields }
+    }
+}
 
 // TODO Item operates
 #[command]
 pub async fn save_full_todo_item(
-    pool: State<'_, SqlitePool>,
+    service: State<'_, TodoService>,
     TodoItem {
         message,
         priority,
         done,
+        estimate,
+        due,
         tags,
+        ..
     }: TodoItem,
-) -> Result<i32, String> {
-    // save tags
-    let ids = tags
-        .pipe(futures::stream::iter)
-        .then(|tag| TagEntity::save(&pool, tag))
-        .try_collect::<Vec<_>>()
-        .await
-        .err_to_str()?;
-
-    // save todo item
-    let todo_item_id = TodoItemEntity::save(&pool, message, priority.into(), done)
-        .await
-        .err_to_str()?;
-    // bind tags with items
-    BindEntity::save_all(
-        &pool,
-        ids.into_iter()
-            .map(|tag_id| BindModel::new(tag_id, todo_item_id)),
-    )
-    .await
-    .err_to_str()?;
-    // return the id of this todo item
-    Ok(todo_item_id)
+) -> Result<i32, CommandError> {
+    Ok(service
+        .save_todo_item(TodoItem {
+            message,
+            priority,
+            done,
+            blocked: false,
+            estimate,
+            due,
+            tags,
+        })
+        .await?)
+}
+
+/// save the item typed in one line like `Fix login bug !high #backend tomorrow`,
+/// `today` is the local date of the user the relative dates count from
+#[command]
+pub async fn quick_add(
+    service: State<'_, TodoService>,
+    line: String,
+    today: NaiveDate,
+) -> Result<(i32, TodoItem), CommandError> {
+    Ok(service.quick_add(&line, today).await?)
 }
 #[command]
 pub async fn fetch_all_todo_item(
-    pool: State<'_, SqlitePool>,
-) -> Result<Vec<(i32, TodoItem)>, String> {
-    // fetch all tags
-    let all_tags = TagEntity::fetch_all(&pool, None)
-        .await
-        .err_to_str()?
-        .into_iter()
-        .map(|TagModel { id, value }| (id, value))
-        .collect::<HashMap<_, _>>();
-    // fetch all tags-item bind;
-    let mut all_binds = BindEntity::fetch_all(&pool)
-        .await
-        .err_to_str()?
-        .into_iter()
-        .fold(
-            HashMap::<i32, Vec<i32>>::new(),
-            |mut map, BindModel { tag_id, item_id }| {
-                map.entry(item_id)
-                    .and_modify(|v| v.push(tag_id))
-                    .or_insert_with(|| vec![tag_id]);
-                map
-            },
-        );
-    // fetch all todo items with its id
-    let all_todo_items = TodoItemEntity::fetch_all(&pool, None)
-        .await
-        .err_to_str()?
-        .into_iter()
-        .map(
-            |TodoItemModel {
-                 id,
-                 message,
-                 priority,
-                 done,
-             }| {
-                (
-                    id,
-                    TodoItem {
-                        message,
-                        priority: priority.into(),
-                        done,
-                        tags: vec![],
-                    },
-                )
-            },
-        )
-        .map(|item| {
-            item.tap_mut(|(item_id, item)| {
-                if let Some(vec) = all_binds.remove(item_id) {
-                    item.tags.extend(
-                        vec.into_iter()
-                            .filter_map(|tag_id| all_tags.get(&tag_id))
-                            .cloned(),
-                    )
-                }
-            })
-        })
-        .collect::<Vec<_>>();
-    // bind the tags and todo items
+    service: State<'_, TodoService>,
+) -> Result<Vec<(i32, TodoItem)>, CommandError> {
+    Ok(service.fetch_all_todo_item().await?)
+}
 
-    Ok(all_todo_items)
+/// `None` clears the due date
+#[command]
+pub async fn edit_due(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    due: Option<NaiveDate>,
+) -> Result<(), CommandError> {
+    Ok(service.edit_due(item_id, due).await?)
 }
 
 #[command]
 pub async fn edit_message(
-    pool: State<'_, SqlitePool>,
+    service: State<'_, TodoService>,
     item_id: i32,
     new_message: String,
-) -> Result<(), String> {
-    // update message
-    TodoItemEntity::update_message(&pool, item_id, new_message)
-        .await
-        .err_to_str()
+) -> Result<(), CommandError> {
+    Ok(service.edit_message(item_id, new_message).await?)
 }
 
 #[command]
 pub async fn edit_priority(
-    pool: State<'_, SqlitePool>,
+    service: State<'_, TodoService>,
     item_id: i32,
     priority: PriorityLevel,
-) -> Result<(), String> {
-    // update priority
-    TodoItemEntity::update_priority(&pool, item_id, priority.into())
-        .await
-        .err_to_str()
+) -> Result<(), CommandError> {
+    Ok(service.edit_priority(item_id, priority).await?)
 }
 
 #[command]
-pub async fn state_revert(pool: State<'_, SqlitePool>, item_id: i32) -> Result<(), String> {
-    // update done
-    TodoItemEntity::revert_done(&pool, item_id)
-        .await
-        .err_to_str()
+pub async fn state_revert(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<(), CommandError> {
+    Ok(service.revert_done(item_id).await?)
 }
-#[derive(Debug, Serialize, Deserialize)]
-pub enum EditMode {
-    Add,
-    Remove,
+#[command]
+pub async fn set_done(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    done: bool,
+) -> Result<DoneState, CommandError> {
+    Ok(service.set_done(item_id, done).await?)
 }
 #[command]
 pub async fn edit_tag(
-    pool: State<'_, SqlitePool>,
+    service: State<'_, TodoService>,
     item_id: i32,
     mode: EditMode,
     tag_name: Tag,
-) -> Result<i32, String> {
-    let tag_id = TagEntity::save(&pool, &tag_name).await.err_to_str()?;
-
-    match mode {
-        // adding tag , create tag first then bind to the todo item
-        EditMode::Add => BindEntity::save_all(&pool, [BindModel::new(tag_id, item_id)])
-            .await
-            .err_to_str(),
-        // remove tag , remove the bind between the tag and todo item
-        EditMode::Remove => BindEntity::remove(&pool, tag_id, item_id)
-            .await
-            .err_to_str(),
-    }
-    .map(|_| tag_id)
+) -> Result<i32, CommandError> {
+    Ok(service.edit_tag(item_id, mode, tag_name).await?)
+}
+
+#[command]
+pub async fn move_item(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    before_id: Option<i32>,
+    after_id: Option<i32>,
+) -> Result<(), CommandError> {
+    Ok(service.move_item(item_id, before_id, after_id).await?)
+}
+
+#[command]
+pub async fn fetch_notes(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<String, CommandError> {
+    Ok(service.fetch_notes(item_id).await?)
+}
+
+#[command]
+pub async fn edit_notes(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    notes: String,
+) -> Result<(), CommandError> {
+    Ok(service.edit_notes(item_id, notes).await?)
+}
+
+#[command]
+pub async fn render_notes(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<String, CommandError> {
+    Ok(service.render_notes(item_id).await?)
+}
+
+#[command]
+pub async fn search_todo_item(
+    service: State<'_, TodoService>,
+    keyword: String,
+) -> Result<Vec<(i32, TodoItem)>, CommandError> {
+    Ok(service.search_todo_item(keyword).await?)
+}
+
+#[command]
+pub async fn add_attachment(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    path: PathBuf,
+) -> Result<Attachment, CommandError> {
+    Ok(service.add_attachment(item_id, path).await?)
+}
+
+#[command]
+pub async fn fetch_attachments(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<Vec<Attachment>, CommandError> {
+    Ok(service.fetch_attachments(item_id).await?)
+}
+
+/// open the attachment with the default app of the system
+#[command]
+pub async fn open_attachment(
+    service: State<'_, TodoService>,
+    attachment_id: i32,
+) -> Result<(), CommandError> {
+    let path = service.export_attachment(attachment_id).await?;
+    open::that(path).map_err(|err| CommandError {
+        message: err.to_string(),
+        fields: vec![],
+    })
+}
+
+#[command]
+pub async fn remove_attachment(
+    service: State<'_, TodoService>,
+    attachment_id: i32,
+) -> Result<(), CommandError> {
+    Ok(service.remove_attachment(attachment_id).await?)
+}
+
+#[command]
+pub async fn add_comment(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    author: String,
+    body: String,
+) -> Result<Comment, CommandError> {
+    Ok(service.add_comment(item_id, author, body).await?)
+}
+
+#[command]
+pub async fn edit_comment(
+    service: State<'_, TodoService>,
+    comment_id: i32,
+    body: String,
+) -> Result<(), CommandError> {
+    Ok(service.edit_comment(comment_id, body).await?)
+}
+
+#[command]
+pub async fn delete_comment(
+    service: State<'_, TodoService>,
+    comment_id: i32,
+) -> Result<(), CommandError> {
+    Ok(service.delete_comment(comment_id).await?)
+}
+
+#[command]
+pub async fn fetch_comments(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<Vec<Comment>, CommandError> {
+    Ok(service.fetch_comments(item_id).await?)
+}
+
+/// the comments and the priority and done changes of the item, the earliest first
+#[command]
+pub async fn fetch_activity(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<Vec<Activity>, CommandError> {
+    Ok(service.fetch_activity(item_id).await?)
+}
+
+#[command]
+pub async fn add_dependency(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    blocker_id: i32,
+) -> Result<(), CommandError> {
+    Ok(service.add_dependency(item_id, blocker_id).await?)
+}
+
+#[command]
+pub async fn remove_dependency(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    blocker_id: i32,
+) -> Result<(), CommandError> {
+    Ok(service.remove_dependency(item_id, blocker_id).await?)
+}
+
+#[command]
+pub async fn fetch_blockers(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<Vec<(i32, TodoItem)>, CommandError> {
+    Ok(service.fetch_blockers(item_id).await?)
+}
+
+/// only one timer runs at a time, the running one is stopped first
+#[command]
+pub async fn start_timer(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<TimeEntry, CommandError> {
+    Ok(service.start_timer(item_id).await?)
+}
+
+#[command]
+pub async fn stop_timer(
+    service: State<'_, TodoService>,
+) -> Result<Option<TimeEntry>, CommandError> {
+    Ok(service.stop_timer().await?)
+}
+
+#[command]
+pub async fn running_timer(
+    service: State<'_, TodoService>,
+) -> Result<Option<TimeEntry>, CommandError> {
+    Ok(service.running_timer().await?)
+}
+
+#[command]
+pub async fn add_time_entry(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    started_at: DateTime<Utc>,
+    stopped_at: DateTime<Utc>,
+) -> Result<TimeEntry, CommandError> {
+    Ok(service
+        .add_time_entry(item_id, started_at, stopped_at)
+        .await?)
+}
+
+#[command]
+pub async fn edit_time_entry(
+    service: State<'_, TodoService>,
+    entry_id: i32,
+    started_at: DateTime<Utc>,
+    stopped_at: Option<DateTime<Utc>>,
+) -> Result<(), CommandError> {
+    Ok(service
+        .edit_time_entry(entry_id, started_at, stopped_at)
+        .await?)
+}
+
+#[command]
+pub async fn delete_time_entry(
+    service: State<'_, TodoService>,
+    entry_id: i32,
+) -> Result<(), CommandError> {
+    Ok(service.delete_time_entry(entry_id).await?)
+}
+
+#[command]
+pub async fn fetch_time_entries(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<Vec<TimeEntry>, CommandError> {
+    Ok(service.fetch_time_entries(item_id).await?)
+}
+
+/// `utc_offset` is minutes east of UTC, the negated `Date.getTimezoneOffset()`
+#[command]
+pub async fn fetch_time_totals(
+    service: State<'_, TodoService>,
+    utc_offset: i32,
+) -> Result<TimeTotals, CommandError> {
+    Ok(service.fetch_time_totals(utc_offset).await?)
+}
+
+/// `None` clears the estimate
+#[command]
+pub async fn set_estimate(
+    service: State<'_, TodoService>,
+    item_id: i32,
+    estimate: Option<Estimate>,
+) -> Result<(), CommandError> {
+    Ok(service.set_estimate(item_id, estimate).await?)
+}
+
+#[command]
+pub async fn fetch_estimate_by_tag(
+    service: State<'_, TodoService>,
+) -> Result<Vec<EstimateRollup<Tag>>, CommandError> {
+    Ok(service.fetch_estimate_by_tag().await?)
+}
+
+#[command]
+pub async fn fetch_estimate_by_priority(
+    service: State<'_, TodoService>,
+) -> Result<Vec<EstimateRollup<PriorityLevel>>, CommandError> {
+    Ok(service.fetch_estimate_by_priority().await?)
+}
+
+/// the stats of the days from `from` to `to`, both included. `utc_offset` is the same as
+/// [`fetch_time_totals`]
+#[command]
+pub async fn fetch_stats(
+    service: State<'_, TodoService>,
+    from: NaiveDate,
+    to: NaiveDate,
+    utc_offset: i32,
+) -> Result<Stats, CommandError> {
+    Ok(service.fetch_stats(from, to, utc_offset).await?)
+}
+
+#[command]
+pub async fn create_saved_search(
+    service: State<'_, TodoService>,
+    name: String,
+    filter: SearchFilter,
+) -> Result<SavedSearch, CommandError> {
+    Ok(service.create_saved_search(name, filter).await?)
+}
+
+#[command]
+pub async fn edit_saved_search(
+    service: State<'_, TodoService>,
+    search_id: i32,
+    name: String,
+    filter: SearchFilter,
+) -> Result<SavedSearch, CommandError> {
+    Ok(service.edit_saved_search(search_id, name, filter).await?)
 }
 
 #[command]
-pub async fn clean_tag(pool: State<'_, SqlitePool>, item_id: i32) -> Result<(), String> {
-    // remove all bind on todo item
-    BindEntity::remove_bind_item_id(&pool, item_id)
-        .await
-        .err_to_str()
+pub async fn delete_saved_search(
+    service: State<'_, TodoService>,
+    search_id: i32,
+) -> Result<(), CommandError> {
+    Ok(service.delete_saved_search(search_id).await?)
 }
+
 #[command]
-pub async fn delete_todo_item(pool: State<'_, SqlitePool>, item_id: i32) -> Result<(), String> {
-    // remove tag-item bind
-    clean_tag(pool.clone(), item_id).await?;
-    // remove item
-    TodoItemEntity::remove(&pool, item_id).await.err_to_str()?;
+pub async fn fetch_saved_searches(
+    service: State<'_, TodoService>,
+) -> Result<Vec<SavedSearch>, CommandError> {
+    Ok(service.fetch_saved_searches().await?)
+}
 
-    Ok(())
+/// the items match the saved search now
+#[command]
+pub async fn evaluate_saved_search(
+    service: State<'_, TodoService>,
+    search_id: i32,
+) -> Result<Vec<(i32, TodoItem)>, CommandError> {
+    Ok(service.evaluate_saved_search(search_id).await?)
+}
+
+#[command]
+pub async fn clean_tag(service: State<'_, TodoService>, item_id: i32) -> Result<(), CommandError> {
+    Ok(service.clean_tag(item_id).await?)
+}
+#[command]
+pub async fn delete_todo_item(
+    service: State<'_, TodoService>,
+    item_id: i32,
+) -> Result<(), CommandError> {
+    Ok(service.delete_todo_item(item_id).await?)
 }
 
 // tag Operate
 #[command]
-pub async fn fetch_all_tags(pool: State<'_, SqlitePool>) -> Result<Vec<(i32, String)>, String> {
-    // get all tags,group with tag id and value
-    TagEntity::fetch_all(&pool, None)
-        .await
-        .map(|list| {
-            list.into_iter()
-                .map(|TagModel { id, value }| (id, value))
-                .collect()
-        })
-        .err_to_str()
+pub async fn fetch_all_tags(
+    service: State<'_, TodoService>,
+) -> Result<Vec<(i32, Tag, TagMeta)>, CommandError> {
+    Ok(service.fetch_all_tags().await?)
 }
 #[command]
 pub async fn fetch_all_tag_todo_item(
-    pool: State<'_, SqlitePool>,
+    service: State<'_, TodoService>,
     tag_id: i32,
-) -> Result<Vec<(i32, TodoItem)>, String> {
-    let item_ids = BindEntity::fetch_all_tag_id(&pool, tag_id)
-        .await
-        .err_to_str()?;
-
-    let mut resp = Vec::new();
-    for TodoItemModel {
-        id,
-        message,
-        priority,
-        done,
-    } in TodoItemEntity::find_all_by_id(&pool, item_ids)
-        .await
-        .err_to_str()?
-    {
-        resp.push((
-            id,
-            TodoItem {
-                message,
-                priority: priority.into(),
-                done,
-                tags: TagEntity::find_all_by_id(
-                    &pool,
-                    BindEntity::fetch_all_item_id(&pool, id)
-                        .await
-                        .err_to_str()?,
-                )
-                .await
-                .err_to_str()?
-                .into_iter()
-                .map(|TagModel { value, .. }| value)
-                .collect(),
-            },
-        ))
-    }
-
-    Ok(resp)
+    include_descendants: Option<bool>,
+) -> Result<Vec<(i32, TodoItem)>, CommandError> {
+    Ok(service
+        .fetch_all_tag_todo_item(tag_id, include_descendants.unwrap_or_default())
+        .await?)
+}
+#[command]
+pub async fn fetch_tag_tree(service: State<'_, TodoService>) -> Result<Vec<TagNode>, CommandError> {
+    Ok(service.fetch_tag_tree().await?)
+}
+#[command]
+pub async fn move_tag(
+    service: State<'_, TodoService>,
+    tag_id: i32,
+    parent_id: Option<i32>,
+) -> Result<(), CommandError> {
+    Ok(service.move_tag(tag_id, parent_id).await?)
+}
+#[command]
+pub async fn merge_tags(
+    service: State<'_, TodoService>,
+    source_ids: Vec<i32>,
+    target_id: i32,
+) -> Result<(), CommandError> {
+    Ok(service.merge_tags(source_ids, target_id).await?)
 }
 #[command]
 pub async fn rename_tag(
-    pool: State<'_, SqlitePool>,
+    service: State<'_, TodoService>,
     tag_id: i32,
     tag_name: Tag,
-) -> Result<(), String> {
-    TagEntity::edit(&pool, tag_id, &tag_name).await.err_to_str()
+) -> Result<(), CommandError> {
+    Ok(service.rename_tag(tag_id, tag_name).await?)
 }
 #[command]
-pub async fn create_tag(pool: State<'_, SqlitePool>, tag_name: Tag) -> Result<i32, String> {
-    TagEntity::save(&pool, &tag_name).await.err_to_str()
+pub async fn create_tag(
+    service: State<'_, TodoService>,
+    tag_name: Tag,
+) -> Result<i32, CommandError> {
+    Ok(service.create_tag(tag_name).await?)
 }
 #[command]
-pub async fn get_tag_id(pool: State<'_, SqlitePool>, tag_name: Tag) -> Result<i32, String> {
-    TagEntity::get_id(&pool, &tag_name).await.err_to_str()
+pub async fn get_tag_id(
+    service: State<'_, TodoService>,
+    tag_name: Tag,
+) -> Result<i32, CommandError> {
+    Ok(service.get_tag_id(tag_name).await?)
 }
 #[command]
-pub async fn delete_tag(pool: State<'_, SqlitePool>, tag_name: Tag) -> Result<(), String> {
-    let tag_id = TagEntity::get_id(&pool, &tag_name).await.err_to_str()?;
-
-    BindEntity::remove_bind_tag_id(&pool, tag_id)
-        .await
-        .err_to_str()?;
-    // remove this
-    TagEntity::remove(&pool, &tag_name).await.err_to_str()?;
-
-    Ok(())
+pub async fn delete_tag(
+    service: State<'_, TodoService>,
+    tag_name: Tag,
+) -> Result<(), CommandError> {
+    Ok(service.delete_tag(tag_name).await?)
+}
+#[command]
+pub async fn update_tag_meta(
+    service: State<'_, TodoService>,
+    tag_id: i32,
+    meta: TagMeta,
+) -> Result<(), CommandError> {
+    Ok(service.update_tag_meta(tag_id, meta).await?)
+}
+#[command]
+pub async fn reorder_tags(
+    service: State<'_, TodoService>,
+    tag_ids: Vec<i32>,
+) -> Result<(), CommandError> {
+    Ok(service.reorder_tags(tag_ids).await?)
+}
+#[command]
+pub async fn fetch_tag_stats(
+    service: State<'_, TodoService>,
+) -> Result<Vec<TagStats>, CommandError> {
+    Ok(service.fetch_tag_stats().await?)
+}
+#[command]
+pub async fn prune_unused_tags(
+    service: State<'_, TodoService>,
+    dry_run: bool,
+) -> Result<Vec<(i32, Tag)>, CommandError> {
+    Ok(service.prune_unused_tags(dry_run).await?)
+}
+#[command]
+pub async fn suggest_tags(
+    service: State<'_, TodoService>,
+    prefix: String,
+    limit: u64,
+) -> Result<Vec<(i32, Tag)>, CommandError> {
+    Ok(service.suggest_tags(prefix, limit).await?)
+}
+#[command]
+pub async fn bulk_edit(
+    service: State<'_, TodoService>,
+    target: BulkTarget,
+    operate: BulkOperate,
+) -> Result<Vec<BulkResult>, CommandError> {
+    Ok(service.bulk_edit(target, operate).await?)
 }
@@ -0,0 +1,27 @@
+import {invoke} from "@tauri-apps/api";
+
+export interface FieldError {
+    field: string,
+    message: string
+}
+
+// the rejected value of every command, `fields` is empty for the errors other than validation
+class CommandError extends Error {
+    readonly fields: FieldError[]
+
+    constructor(error: { message: string, fields: FieldError[] }) {
+        super(error.message);
+        this.name = "CommandError";
+        this.fields = error.fields;
+    }
+}
+
+// `invoke` rejecting with a `CommandError`, so the callers can show `message`
+export async function invokeCommand<T>(cmd: string, args?: Record<string, unknown>): Promise<T> {
+    return await invoke<T>(cmd, args)
+        .catch((error) => {
+            throw new CommandError(error)
+        })
+}
+
+export default CommandError
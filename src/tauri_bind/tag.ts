@@ -1,4 +1,4 @@
-import {invoke} from "@tauri-apps/api";
+import {invokeCommand} from "./CommandError";
 import {TodoItem} from "./todoItem";
 import IdIsUndefinedError from "./IdIsUndefinedError";
 
@@ -7,27 +7,37 @@ export interface TagInterface {
     value: string
 }
 
+// the display information of a tag, `color` is a hex color like `#ff8800`
+export interface TagMeta {
+    color: string | null,
+    icon: string | null,
+    description: string | null,
+    position: number
+}
+
 export class Tag {
     private id?: number
     private readonly value: string
+    private readonly meta?: TagMeta
 
-    constructor(value: string, id?: number,) {
+    constructor(value: string, id?: number, meta?: TagMeta) {
         this.id = id;
         this.value = value;
+        this.meta = meta;
     }
 
     public static async fetch(tagName: string): Promise<Tag> {
-        return await invoke<number>("get_tag_id", {tagName: tagName})
+        return await invokeCommand<number>("get_tag_id", {tagName: tagName})
             .then((id) => {
                 return new Tag(tagName, id)
             })
     }
 
     public static async fetchAll(): Promise<Tag[]> {
-        return await invoke<[number, string][]>("fetch_all_tags",)
+        return await invokeCommand<[number, string, TagMeta][]>("fetch_all_tags",)
             .then((list) => {
-                return list.map(([id, value]) => {
-                    return new Tag(value, id)
+                return list.map(([id, value, meta]) => {
+                    return new Tag(value, id, meta)
                 })
             });
     }
@@ -46,6 +56,10 @@ export class Tag {
         return this.value
     }
 
+    public getMeta(): TagMeta | undefined {
+        return this.meta
+    }
+
     public checkEquals(rhs: Tag): boolean {
         return this.value == rhs.value
     }
@@ -55,7 +69,7 @@ export class Tag {
     }
 
     public async create(): Promise<void> {
-        await invoke<number>("create_tag", {tagName: this.value})
+        await invokeCommand<number>("create_tag", {tagName: this.value})
             .then((id) => {
                 this.id = id
             })
@@ -63,7 +77,7 @@ export class Tag {
 
     public async rename(name: string): Promise<void> {
         if (this.id != undefined) {
-            await invoke<void>("rename_tag", {tagId: this.id, tagName: name})
+            await invokeCommand<void>("rename_tag", {tagId: this.id, tagName: name})
         }
         self.name = name
 
@@ -71,7 +85,7 @@ export class Tag {
 
     public async fetchAllRelateTodoItem(): Promise<TodoItem[]> {
         return await this.whenIdValid((id) => {
-            return invoke<[number, TodoItem][]>("fetch_all_tag_todo_item", {tagId: id})
+            return invokeCommand<[number, TodoItem][]>("fetch_all_tag_todo_item", {tagId: id})
                 .then((list) => {
                     return list.map(([id, item]) => {
                         return TodoItem.withId(id, item)
@@ -82,7 +96,7 @@ export class Tag {
     }
     // Warning: call this function should consume this object
     public async removeThis() {
-        await invoke("delete_tag", {tagName: this.value});
+        await invokeCommand("delete_tag", {tagName: this.value});
         this.id = undefined
     }
 
@@ -1,4 +1,4 @@
-import {invoke} from "@tauri-apps/api";
+import {invokeCommand} from "./CommandError";
 import IdIsUndefinedError from "./IdIsUndefinedError";
 import {Tag, TagInterface} from "./tag";
 
@@ -35,7 +35,7 @@ export class TodoItem {
     }
 
     public static async fetchAll(): Promise<TodoItem[]> {
-        return await invoke<[number, TodoItem][]>("fetch_all_todo_item")
+        return await invokeCommand<[number, TodoItem][]>("fetch_all_todo_item")
             .then((list) => {
                 return list.map(([id, item]) => {
                     return TodoItem.withId(id, item)
@@ -53,7 +53,7 @@ export class TodoItem {
     }
 
     public async save(): Promise<void> {
-        await invoke<number>("save_full_todo_item", {TodoItem: this})
+        await invokeCommand<number>("save_full_todo_item", {TodoItem: this})
             .then((id) => {
                 this.id = id
             })
@@ -61,14 +61,14 @@ export class TodoItem {
 
     public async editMessage(msg: string): Promise<void> {
         if (this.id != undefined) {
-            await invoke<void>("edit_message", {itemId: this.id, newMessage: msg})
+            await invokeCommand<void>("edit_message", {itemId: this.id, newMessage: msg})
         }
         this.message = msg
     }
 
     public async editPriority(priority: Priority): Promise<void> {
         if (this.id != undefined) {
-            await invoke<void>("edit_priority", {itemId: this.id, priority: priority})
+            await invokeCommand<void>("edit_priority", {itemId: this.id, priority: priority})
         }
         this.priority = priority
     }
@@ -76,7 +76,7 @@ export class TodoItem {
     public async revertState(): Promise<void> {
         if (this.id != undefined) {
 
-            await invoke<void>("state_revert", {itemId: this.id})
+            await invokeCommand<void>("state_revert", {itemId: this.id})
         }
         this.done = !this.done
     }
@@ -90,7 +90,7 @@ export class TodoItem {
         }
 
         if (this.id != undefined) {
-            await invoke<number>("edit_tag", {
+            await invokeCommand<number>("edit_tag", {
                 itemId: this.id, mode: mode, tagName: tagObj.getValue()
             }).then(tagObj.setId);
         }
@@ -105,7 +105,7 @@ export class TodoItem {
 
     public async cleanTags() {
         if (this.id != undefined) {
-            await invoke<void>("clean_tag", {itemId: this.id})
+            await invokeCommand<void>("clean_tag", {itemId: this.id})
         }
 
         this.tags = this.tags.filter(() => {
@@ -119,7 +119,7 @@ export class TodoItem {
             throw new IdIsUndefinedError('TodoItem')
         }
 
-        await invoke<void>("delete_todo_item", {itemId: this.id});
+        await invokeCommand<void>("delete_todo_item", {itemId: this.id});
         this.id = undefined
     }
 }